@@ -0,0 +1,260 @@
+//! Pinning racing threads to CPUs. Where the threads run decides
+//! how far apart the contending cores are, which changes both how
+//! often updates get lost and what an atomic costs.
+
+use crate::topology::{self, Topology};
+use serde::{Serialize, Serializer};
+use std::{fmt, io};
+
+/// How racing threads are assigned to CPUs. When there are more
+/// threads than CPUs to put them on, they wrap around.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pin {
+    /// Wherever the OS likes.
+    None,
+    /// Fill one core's hardware threads before moving on to the
+    /// next core.
+    Compact,
+    /// One thread per core before doubling up on any of them.
+    Spread,
+    /// Only the hardware threads of a single core.
+    Siblings,
+    /// Take turns between NUMA nodes, spreading out within each.
+    Numa,
+    /// Exactly these CPUs, in order.
+    Cpus(Vec<usize>),
+}
+
+impl Pin {
+    /// Parses a strategy name or a CPU list like `0-3,8`.
+    pub fn parse(s: &str) -> Result<Self, String> {
+        Ok(match s {
+            "none" => Self::None,
+            "compact" => Self::Compact,
+            "spread" => Self::Spread,
+            "siblings" => Self::Siblings,
+            "numa" => Self::Numa,
+            _ => match topology::parse_list(s) {
+                Some(cpus) if !cpus.is_empty() => Self::Cpus(cpus),
+                _ => {
+                    return Err(format!(
+                        "expected none, compact, spread, siblings, numa or a CPU list like 0-3,8, not {s:?}"
+                    ))
+                }
+            },
+        })
+    }
+
+    /// The CPU each of `threads` threads goes on, or `None` if
+    /// they aren't pinned.
+    pub fn plan(&self, threads: usize, topology: &Topology) -> Result<Option<Vec<usize>>, String> {
+        if *self == Self::None {
+            return Ok(None);
+        }
+        if !cfg!(target_os = "linux") {
+            return Err("pinning threads is only supported on Linux".to_owned());
+        }
+
+        let allowed = allowed().map_err(|e| format!("can't read this process's CPUs: {e}"))?;
+        let usable = Topology {
+            cpus: topology
+                .cpus
+                .iter()
+                .filter(|c| allowed.contains(&c.id))
+                .copied()
+                .collect(),
+        };
+        self.assign(threads, &usable).map(Some)
+    }
+
+    /// The CPU each of `threads` threads goes on, picked from every
+    /// CPU in `topology`.
+    fn assign(&self, threads: usize, topology: &Topology) -> Result<Vec<usize>, String> {
+        let mut cpus = topology.cpus.clone();
+        let order: Vec<usize> = match self {
+            Self::None => unreachable!(),
+            Self::Compact => {
+                cpus.sort_by_key(|c| (c.node, c.package, c.core, c.id));
+                cpus.iter().map(|c| c.id).collect()
+            }
+            Self::Spread => {
+                cpus.sort_by_cached_key(|c| (topology.sibling_rank(c), c.node, c.package, c.core));
+                cpus.iter().map(|c| c.id).collect()
+            }
+            Self::Siblings => {
+                let core = cpus
+                    .iter()
+                    .find(|c| cpus.iter().filter(|o| o.is_sibling(c)).count() > 1)
+                    .copied()
+                    .ok_or("no core has more than one hardware thread to pin to")?;
+                cpus.iter()
+                    .filter(|c| c.is_sibling(&core))
+                    .map(|c| c.id)
+                    .collect()
+            }
+            Self::Numa => {
+                cpus.sort_by_cached_key(|c| (topology.sibling_rank(c), c.package, c.core));
+                let per_node: Vec<Vec<usize>> = topology
+                    .nodes()
+                    .into_iter()
+                    .map(|node| {
+                        cpus.iter()
+                            .filter(|c| c.node == node)
+                            .map(|c| c.id)
+                            .collect()
+                    })
+                    .filter(|ids: &Vec<usize>| !ids.is_empty())
+                    .collect();
+                // Round-robin over the nodes, one CPU from each.
+                let longest = per_node.iter().map(Vec::len).max().unwrap_or(0);
+                (0..longest)
+                    .flat_map(|i| per_node.iter().filter_map(move |ids| ids.get(i)))
+                    .copied()
+                    .collect()
+            }
+            Self::Cpus(ids) => {
+                if let Some(id) = ids.iter().find(|id| !cpus.iter().any(|c| c.id == **id)) {
+                    return Err(format!(
+                        "CPU {id} isn't online or isn't available to this process"
+                    ));
+                }
+                ids.clone()
+            }
+        };
+
+        if order.is_empty() {
+            return Err("no CPUs to pin to".to_owned());
+        }
+        Ok((0..threads).map(|t| order[t % order.len()]).collect())
+    }
+}
+
+impl fmt::Display for Pin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::None => f.write_str("none"),
+            Self::Compact => f.write_str("compact"),
+            Self::Spread => f.write_str("spread"),
+            Self::Siblings => f.write_str("siblings"),
+            Self::Numa => f.write_str("numa"),
+            Self::Cpus(ids) => f.write_str(&join(ids)),
+        }
+    }
+}
+
+impl Serialize for Pin {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// CPU ids, comma-separated.
+pub fn join(ids: &[usize]) -> String {
+    let ids: Vec<_> = ids.iter().map(usize::to_string).collect();
+    ids.join(",")
+}
+
+/// Moves the calling thread onto `cpu`, and keeps it there.
+#[cfg(target_os = "linux")]
+pub fn pin(cpu: usize) -> io::Result<()> {
+    // SAFETY: `cpu_set_t` is plain data, and zeroed is empty.
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    if cpu >= 8 * std::mem::size_of_val(&set) {
+        return Err(io::ErrorKind::InvalidInput.into());
+    }
+    // SAFETY: `set` is a valid set, and `cpu` is in it.
+    unsafe { libc::CPU_SET(cpu, &mut set) };
+    // SAFETY: pid 0 is the calling thread, and `set` is as big as
+    // we say it is.
+    let ret = unsafe { libc::sched_setaffinity(0, std::mem::size_of_val(&set), &set) };
+    match ret {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin(_: usize) -> io::Result<()> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+/// The CPUs this process may run on.
+#[cfg(target_os = "linux")]
+fn allowed() -> io::Result<Vec<usize>> {
+    // SAFETY: as in `pin`.
+    let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+    // SAFETY: pid 0 is the calling thread, and `set` is as big as
+    // we say it is.
+    let ret = unsafe { libc::sched_getaffinity(0, std::mem::size_of_val(&set), &mut set) };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let count = 8 * std::mem::size_of_val(&set);
+    // SAFETY: `set` was filled in by the kernel.
+    Ok((0..count)
+        .filter(|&cpu| unsafe { libc::CPU_ISSET(cpu, &set) })
+        .collect())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn allowed() -> io::Result<Vec<usize>> {
+    Err(io::ErrorKind::Unsupported.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::topology::Cpu;
+
+    /// Two packages of two cores with two hardware threads each,
+    /// numbered the way Linux usually does: the first thread of
+    /// every core, then the second. Each package is a NUMA node.
+    fn machine() -> Topology {
+        let cpus = (0..8)
+            .map(|id| Cpu {
+                id,
+                package: id % 4 / 2,
+                core: id % 2,
+                node: id % 4 / 2,
+            })
+            .collect();
+        Topology { cpus }
+    }
+
+    #[test]
+    fn strategies_order_cpus_by_topology() {
+        let plan = |pin: Pin| pin.assign(8, &machine()).unwrap();
+        assert_eq!(plan(Pin::Compact), [0, 4, 1, 5, 2, 6, 3, 7]);
+        assert_eq!(plan(Pin::Spread), [0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(plan(Pin::Siblings), [0, 4, 0, 4, 0, 4, 0, 4]);
+        assert_eq!(plan(Pin::Numa), [0, 2, 1, 3, 4, 6, 5, 7]);
+    }
+
+    #[test]
+    fn threads_wrap_around_the_cpus() {
+        let pin = Pin::Cpus(vec![5, 2]);
+        assert_eq!(pin.assign(5, &machine()).unwrap(), [5, 2, 5, 2, 5]);
+        assert_eq!(pin.assign(1, &machine()).unwrap(), [5]);
+        assert!(pin.assign(0, &machine()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn rejects_what_it_cant_pin_to() {
+        assert!(Pin::Cpus(vec![8]).assign(1, &machine()).is_err());
+        assert!(Pin::Compact.assign(1, &Topology { cpus: vec![] }).is_err());
+
+        let no_smt = Topology {
+            cpus: machine().cpus.into_iter().take(4).collect(),
+        };
+        assert!(Pin::Siblings.assign(1, &no_smt).is_err());
+    }
+
+    #[test]
+    fn parses_strategies_and_cpu_lists() {
+        assert_eq!(Pin::parse("spread"), Ok(Pin::Spread));
+        assert_eq!(Pin::parse("0-2,7"), Ok(Pin::Cpus(vec![0, 1, 2, 7])));
+        assert!(Pin::parse("").is_err());
+        assert!(Pin::parse("fast").is_err());
+        assert_eq!(Pin::parse("1-3").unwrap().to_string(), "1,2,3");
+    }
+}
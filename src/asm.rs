@@ -0,0 +1,183 @@
+//! The `asm` module does the unsynchronized read-modify-write in
+//! inline assembly, so the machine code is the same no matter
+//! what the optimizer thinks of the data race. Architectures
+//! without an implementation fall back to [`SharedVolatile`].
+//!
+//! [`SharedVolatile`]: crate::unsync::SharedVolatile
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+pub use imp::SharedAsm;
+#[cfg(target_arch = "x86_64")]
+pub use imp::SharedAsmXorMem;
+
+#[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+pub type SharedAsm = crate::unsync::SharedVolatile;
+
+#[cfg(any(target_arch = "x86_64", target_arch = "aarch64"))]
+mod imp {
+    use crate::{
+        layout::{Arena, Shared},
+        op::{OpKind, Operation},
+        race::Race,
+    };
+    use std::{arch::asm, cell::UnsafeCell};
+
+    /// A plain load, op, and store, as three separate instructions.
+    /// Swaps are a plain store.
+    #[derive(Clone)]
+    pub struct SharedAsm(Shared<UnsafeCell<u64>>);
+
+    /// Loads from `ptr`, applies `insn` with `operand`, and stores
+    /// the result back, as separate instructions.
+    #[cfg(target_arch = "x86_64")]
+    macro_rules! load_op_store {
+        ($insn:literal, $ptr:expr, $operand:expr) => {
+            asm!(
+                "mov {tmp}, qword ptr [{ptr}]",
+                concat!($insn, " {tmp}, {operand}"),
+                "mov qword ptr [{ptr}], {tmp}",
+                ptr = in(reg) $ptr,
+                operand = in(reg) $operand,
+                tmp = out(reg) _,
+                options(nostack),
+            )
+        };
+    }
+
+    #[cfg(target_arch = "aarch64")]
+    macro_rules! load_op_store {
+        ($insn:literal, $ptr:expr, $operand:expr) => {
+            asm!(
+                "ldr {tmp}, [{ptr}]",
+                concat!($insn, " {tmp}, {tmp}, {operand}"),
+                "str {tmp}, [{ptr}]",
+                ptr = in(reg) $ptr,
+                operand = in(reg) $operand,
+                tmp = out(reg) _,
+                options(nostack, preserves_flags),
+            )
+        };
+    }
+
+    impl Race for SharedAsm {
+        type Word = u64;
+
+        fn new(arena: &Arena) -> Self {
+            Self(arena.place(UnsafeCell::new(0)))
+        }
+
+        fn get(&self) -> u64 {
+            unsafe { self.0.get().read_volatile() }
+        }
+
+        fn address(&self) -> usize {
+            self.0.as_ptr() as usize
+        }
+
+        #[cfg(target_arch = "x86_64")]
+        fn apply<O: Operation>(&self, operand: u64) {
+            let ptr = self.0.get();
+            // SAFETY: the pointer is valid and aligned; the race is
+            // the point, and the compiler never sees it.
+            unsafe {
+                match O::KIND {
+                    OpKind::Xor => load_op_store!("xor", ptr, operand),
+                    OpKind::Add => load_op_store!("add", ptr, operand),
+                    OpKind::Sub => load_op_store!("sub", ptr, operand),
+                    OpKind::Or => load_op_store!("or", ptr, operand),
+                    OpKind::And => load_op_store!("and", ptr, operand),
+                    OpKind::Swap => asm!(
+                        "mov qword ptr [{ptr}], {operand}",
+                        ptr = in(reg) ptr,
+                        operand = in(reg) operand,
+                        options(nostack, preserves_flags),
+                    ),
+                }
+            }
+        }
+
+        #[cfg(target_arch = "aarch64")]
+        fn apply<O: Operation>(&self, operand: u64) {
+            let ptr = self.0.get();
+            // SAFETY: the pointer is valid and aligned; the race is
+            // the point, and the compiler never sees it.
+            unsafe {
+                match O::KIND {
+                    OpKind::Xor => load_op_store!("eor", ptr, operand),
+                    OpKind::Add => load_op_store!("add", ptr, operand),
+                    OpKind::Sub => load_op_store!("sub", ptr, operand),
+                    OpKind::Or => load_op_store!("orr", ptr, operand),
+                    OpKind::And => load_op_store!("and", ptr, operand),
+                    OpKind::Swap => asm!(
+                        "str {operand}, [{ptr}]",
+                        ptr = in(reg) ptr,
+                        operand = in(reg) operand,
+                        options(nostack, preserves_flags),
+                    ),
+                }
+            }
+        }
+    }
+
+    // SAFETY: still unsafe.
+    unsafe impl Send for SharedAsm {}
+    unsafe impl Sync for SharedAsm {}
+
+    /// A single `op [mem], reg` without the `lock` prefix, like
+    /// `xor [mem], reg`. It's one instruction, but the core still
+    /// does the load and the store separately.
+    #[cfg(target_arch = "x86_64")]
+    #[derive(Clone)]
+    pub struct SharedAsmXorMem(Shared<UnsafeCell<u64>>);
+
+    #[cfg(target_arch = "x86_64")]
+    macro_rules! op_mem {
+        ($insn:literal, $ptr:expr, $operand:expr) => {
+            asm!(
+                concat!($insn, " qword ptr [{ptr}], {operand}"),
+                ptr = in(reg) $ptr,
+                operand = in(reg) $operand,
+                options(nostack),
+            )
+        };
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    impl Race for SharedAsmXorMem {
+        type Word = u64;
+
+        fn new(arena: &Arena) -> Self {
+            Self(arena.place(UnsafeCell::new(0)))
+        }
+
+        fn get(&self) -> u64 {
+            unsafe { self.0.get().read_volatile() }
+        }
+
+        fn address(&self) -> usize {
+            self.0.as_ptr() as usize
+        }
+
+        fn apply<O: Operation>(&self, operand: u64) {
+            let ptr = self.0.get();
+            // SAFETY: the pointer is valid and aligned; the race is
+            // the point, and the compiler never sees it.
+            unsafe {
+                match O::KIND {
+                    OpKind::Xor => op_mem!("xor", ptr, operand),
+                    OpKind::Add => op_mem!("add", ptr, operand),
+                    OpKind::Sub => op_mem!("sub", ptr, operand),
+                    OpKind::Or => op_mem!("or", ptr, operand),
+                    OpKind::And => op_mem!("and", ptr, operand),
+                    OpKind::Swap => op_mem!("mov", ptr, operand),
+                }
+            }
+        }
+    }
+
+    // SAFETY: still unsafe.
+    #[cfg(target_arch = "x86_64")]
+    unsafe impl Send for SharedAsmXorMem {}
+    #[cfg(target_arch = "x86_64")]
+    unsafe impl Sync for SharedAsmXorMem {}
+}
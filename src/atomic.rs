@@ -0,0 +1,123 @@
+//! The `atomic` module uses processor-intrinsics to do
+//! read-modify-writes atomically.
+
+use crate::{
+    layout::{Arena, Shared},
+    op::{self, Operation},
+    race::{AnyWidth, Race},
+    word::{AtomicWord, Word},
+};
+use std::{marker::PhantomData, sync::atomic::Ordering};
+
+/// The memory orderings a [`SharedAtomic`] uses, chosen at compile
+/// time so each one gets its own hot loop.
+pub trait Orderings: 'static {
+    /// Used by `get`.
+    const LOAD: Ordering;
+    /// Used by every read-modify-write.
+    const RMW: Ordering;
+}
+
+/// Relaxed everywhere. This is the original control group.
+pub struct Relaxed;
+
+/// Acquire loads and Release read-modify-writes.
+pub struct AcquireRelease;
+
+/// Acquire loads and AcqRel read-modify-writes.
+pub struct AcqRel;
+
+/// SeqCst everywhere.
+pub struct SeqCst;
+
+impl Orderings for Relaxed {
+    const LOAD: Ordering = Ordering::Relaxed;
+    const RMW: Ordering = Ordering::Relaxed;
+}
+
+impl Orderings for AcquireRelease {
+    const LOAD: Ordering = Ordering::Acquire;
+    const RMW: Ordering = Ordering::Release;
+}
+
+impl Orderings for AcqRel {
+    const LOAD: Ordering = Ordering::Acquire;
+    const RMW: Ordering = Ordering::AcqRel;
+}
+
+impl Orderings for SeqCst {
+    const LOAD: Ordering = Ordering::SeqCst;
+    const RMW: Ordering = Ordering::SeqCst;
+}
+
+pub struct SharedAtomic<O = Relaxed, W: Word = u64>(Shared<W::Atomic>, PhantomData<fn() -> O>);
+
+// Deriving would needlessly require `O: Clone`.
+impl<O, W: Word> Clone for SharedAtomic<O, W> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone(), PhantomData)
+    }
+}
+
+impl<O: Orderings, W: Word> Race for SharedAtomic<O, W> {
+    type Word = W;
+
+    fn new(arena: &Arena) -> Self {
+        Self(arena.place(W::Atomic::default()), PhantomData)
+    }
+
+    fn get(&self) -> W {
+        self.0.load(O::LOAD)
+    }
+
+    fn address(&self) -> usize {
+        self.0.as_ptr() as usize
+    }
+
+    fn apply<Op: Operation>(&self, operand: W) {
+        op::atomic_rmw::<Op, W>(&self.0, operand, O::RMW);
+    }
+}
+
+impl<O: Orderings, W: Word> AnyWidth for SharedAtomic<O, W> {
+    type At<V: Word> = SharedAtomic<O, V>;
+}
+
+/// Does each operation as an atomic load followed by a separate
+/// atomic store.
+/// There's no data race as far as Rust is concerned, but another
+/// thread can still slip in between the two and have its update
+/// overwritten. Lost updates without the undefined behavior.
+pub struct SharedLoadStore<W: Word = u64>(Shared<W::Atomic>);
+
+// Deriving would require `W::Atomic: Clone`, which atomics aren't.
+impl<W: Word> Clone for SharedLoadStore<W> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<W: Word> Race for SharedLoadStore<W> {
+    type Word = W;
+
+    fn new(arena: &Arena) -> Self {
+        Self(arena.place(W::Atomic::default()))
+    }
+
+    fn get(&self) -> W {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn address(&self) -> usize {
+        self.0.as_ptr() as usize
+    }
+
+    fn apply<O: Operation>(&self, operand: W) {
+        let current = self.0.load(Ordering::Relaxed);
+        self.0.store(O::apply(current, operand), Ordering::Relaxed);
+    }
+}
+
+impl<W: Word> AnyWidth for SharedLoadStore<W> {
+    type At<V: Word> = SharedLoadStore<V>;
+}
@@ -0,0 +1,69 @@
+//! The `cas` module does read-modify-writes the way LL/SC
+//! machines have to: load, compute, and retry a compare-exchange
+//! until nobody else got there first.
+
+use crate::{
+    layout::{Arena, Shared},
+    op::Operation,
+    race::{AnyWidth, Race},
+    word::{AtomicWord, Word},
+};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+pub struct SharedCas<W: Word = u64> {
+    value: Shared<W::Atomic>,
+    /// Failed compare-exchanges on this clone. Only its own thread
+    /// touches it, so a plain load and store is enough.
+    retries: AtomicU64,
+}
+
+// Every clone starts its own retry count.
+impl<W: Word> Clone for SharedCas<W> {
+    fn clone(&self) -> Self {
+        Self {
+            value: self.value.clone(),
+            retries: AtomicU64::new(0),
+        }
+    }
+}
+
+impl<W: Word> Race for SharedCas<W> {
+    type Word = W;
+
+    fn new(arena: &Arena) -> Self {
+        Self {
+            value: arena.place(W::Atomic::default()),
+            retries: AtomicU64::new(0),
+        }
+    }
+
+    fn get(&self) -> W {
+        self.value.load(Ordering::Relaxed)
+    }
+
+    fn address(&self) -> usize {
+        self.value.as_ptr() as usize
+    }
+
+    fn apply<O: Operation>(&self, operand: W) {
+        let mut current = self.value.load(Ordering::Relaxed);
+        while let Err(actual) = self.value.compare_exchange_weak(
+            current,
+            O::apply(current, operand),
+            Ordering::Relaxed,
+            Ordering::Relaxed,
+        ) {
+            current = actual;
+            let retries = self.retries.load(Ordering::Relaxed);
+            self.retries.store(retries + 1, Ordering::Relaxed);
+        }
+    }
+
+    fn counters(&self) -> Vec<(&'static str, u64)> {
+        vec![("retries", self.retries.load(Ordering::Relaxed))]
+    }
+}
+
+impl<W: Word> AnyWidth for SharedCas<W> {
+    type At<V: Word> = SharedCas<V>;
+}
@@ -0,0 +1,152 @@
+use crate::{
+    affinity::Pin, gate::Gate, host::Host, layout::Layout, registry, word::Width,
+    workload::Workload,
+};
+use clap::{builder::RangedU64ValueParser, Parser, ValueEnum};
+use serde::Serialize;
+use std::fmt;
+
+/// Race unsynchronized and atomic read-modify-writes against
+/// each other and see which ones lose updates.
+#[derive(Debug, Clone, Parser, Serialize)]
+#[command(version, about)]
+pub struct Config {
+    /// Number of threads racing on the shared values. Defaults to
+    /// one per CPU this process may run on, and at least two.
+    #[arg(short, long, default_value_t = Host::get().default_threads(), value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub threads: usize,
+
+    /// Number of random values each thread generates.
+    #[arg(short, long, default_value_t = 256, value_parser = RangedU64ValueParser::<usize>::new().range(1..))]
+    pub rounds: usize,
+
+    /// Number of times each random value is applied. Must be even,
+    /// otherwise xors and add/sub pairs don't cancel out.
+    #[arg(short = 'x', long, default_value_t = 2048, value_parser = parse_even)]
+    pub reps: usize,
+
+    /// Comma-separated contestants to race, in order.
+    #[arg(
+        short,
+        long,
+        value_delimiter = ',',
+        default_value = "atomic,unsync",
+        value_parser = registry::names()
+    )]
+    pub contestants: Vec<String>,
+
+    /// What each thread does with its random values.
+    #[arg(short, long, value_enum, default_value_t = Workload::Xor)]
+    pub workload: Workload,
+
+    /// The integer width every contestant races at. Contestants
+    /// built around one width, like `halves`, only run at theirs.
+    #[arg(long, value_enum, default_value_t = Width::U64)]
+    pub width: Width,
+
+    /// Comma-separated layouts for the contestants' shared values.
+    /// Every layout gets its own trials and its own results.
+    #[arg(short, long, value_enum, value_delimiter = ',', default_value = "heap")]
+    pub layouts: Vec<Layout>,
+
+    /// Cache line size in bytes, for the adjacent-lines and padded
+    /// layouts and for telling which values share a line. Defaults
+    /// to what the CPU reports.
+    #[arg(long, value_name = "BYTES", default_value_t = Host::get().line_size, value_parser = parse_power_of_two)]
+    pub line_size: usize,
+
+    /// Which CPUs the racing threads are pinned to: none, compact
+    /// (fill each core's hardware threads first), spread (one
+    /// thread per core first), siblings (one core's hardware
+    /// threads only), numa (take turns between NUMA nodes), or a
+    /// CPU list like 0-3,8. Threads wrap around the CPUs they're
+    /// given.
+    #[arg(short, long, default_value = "none", value_parser = Pin::parse)]
+    pub pin: Pin,
+
+    /// The CPU each thread is pinned to, worked out from `pin`
+    /// before racing.
+    #[arg(skip)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpus: Option<Vec<usize>>,
+
+    /// What holds the racing threads back until all of them are
+    /// ready: nothing, a barrier, or a spinning counter.
+    #[arg(short, long, value_enum, default_value_t = Gate::Barrier)]
+    pub gate: Gate,
+
+    /// How the selected contestants share the race.
+    #[arg(short, long, value_enum, default_value_t = Mode::Interleaved)]
+    pub mode: Mode,
+
+    /// Number of times to rerun the whole race. With more than
+    /// one trial, results are summarized per contestant.
+    #[arg(short = 'n', long, default_value_t = 1, value_parser = clap::value_parser!(u64).range(1..))]
+    pub trials: u64,
+
+    /// Master seed for the random values. Each thread's generator
+    /// is derived from it and the thread's index, so a run can be
+    /// replayed with the same operands. Picked at random if unset.
+    #[arg(short, long)]
+    pub seed: Option<u64>,
+
+    /// Try to explain every corrupted value as the xor of at most
+    /// this many lost operands. The search grows as
+    /// (threads * rounds)^(N - 1), so it stops at 3. Only works
+    /// with the xor workload.
+    #[arg(short, long, value_name = "N", value_parser = clap::value_parser!(u8).range(1..=3))]
+    pub diagnose: Option<u8>,
+
+    /// How to write the results.
+    #[arg(short, long, value_enum, default_value_t = Format::Text)]
+    #[serde(skip)]
+    pub format: Format,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    /// Every contestant applies each rep of the workload
+    /// back-to-back in one hot loop.
+    Interleaved,
+    /// Every contestant gets its own full run on fresh threads.
+    Isolated,
+    /// The same threads run every rep against one contestant
+    /// before moving on to the next.
+    Alternating,
+}
+
+fn parse_even(s: &str) -> Result<usize, String> {
+    let n: usize = s.parse().map_err(|e| format!("{e}"))?;
+    if !n.is_multiple_of(2) {
+        return Err(format!(
+            "{n} is odd; an odd number of reps won't cancel out"
+        ));
+    }
+    Ok(n)
+}
+
+fn parse_power_of_two(s: &str) -> Result<usize, String> {
+    let n: usize = s.parse().map_err(|e| format!("{e}"))?;
+    if !n.is_power_of_two() {
+        return Err(format!("{n} isn't a power of two"));
+    }
+    Ok(n)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Format {
+    /// Human-readable.
+    Text,
+    /// One JSON document with everything, including summaries.
+    Json,
+    /// One row per contestant per trial.
+    Csv,
+}
+
+impl fmt::Display for Mode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
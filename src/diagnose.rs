@@ -0,0 +1,149 @@
+//! Explains a corrupted final value in terms of the updates that
+//! were lost.
+//!
+//! Every operand is xor'd in an even number of times, so anything
+//! left in the final value is the xor of the operands that lost an
+//! odd number of their updates. Replaying every thread's operands
+//! from the seed and searching for a small subset that xors to the
+//! final value tells us which updates raced.
+
+use crate::{cli::Config, harness, word::Width};
+use serde::Serialize;
+use std::{collections::HashMap, fmt};
+
+/// One random value, and where it came from.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Operand {
+    pub thread: usize,
+    pub round: usize,
+    pub value: u128,
+    /// What `value` is padded to when shown.
+    #[serde(skip)]
+    width: Width,
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "thread {} round {} ({})",
+            self.thread,
+            self.round,
+            self.width.hex(self.value)
+        )
+    }
+}
+
+/// The lost operands behind one corrupted value.
+#[derive(Debug, Clone, Serialize)]
+pub struct Diagnosis {
+    /// Whether a small enough set of operands was found.
+    pub explained: bool,
+    pub lost: Vec<Operand>,
+}
+
+/// Every operand every thread xor'd in during a race.
+pub struct OperandLog {
+    operands: Vec<Operand>,
+    index: HashMap<u128, usize>,
+}
+
+impl OperandLog {
+    pub fn replay(config: &Config) -> Self {
+        let operands: Vec<_> = (0..config.threads)
+            .flat_map(|thread| {
+                harness::operands(config, thread)
+                    .enumerate()
+                    .map(move |(round, value)| Operand {
+                        thread,
+                        round,
+                        value,
+                        width: config.width,
+                    })
+            })
+            .collect();
+        let index = operands
+            .iter()
+            .enumerate()
+            .map(|(i, op)| (op.value, i))
+            .collect();
+
+        Self { operands, index }
+    }
+
+    /// The smallest set of at most `max_lost` operands whose xor is
+    /// `value`, if there is one.
+    pub fn explain(&self, value: u128, max_lost: usize) -> Diagnosis {
+        let lost = (1..=max_lost).find_map(|size| {
+            let mut picked = Vec::with_capacity(size);
+            self.search(value, 0, size, &mut picked)
+                .then(|| picked.iter().map(|&i| self.operands[i]).collect())
+        });
+
+        Diagnosis {
+            explained: lost.is_some(),
+            lost: lost.unwrap_or_default(),
+        }
+    }
+
+    /// Looks for `size` operands at or after `start` that xor to
+    /// `target`. The last one is a hash lookup, so this costs
+    /// `O(n^(size - 1))`.
+    fn search(&self, target: u128, start: usize, size: usize, picked: &mut Vec<usize>) -> bool {
+        if size == 1 {
+            return match self.index.get(&target) {
+                Some(&i) if i >= start => {
+                    picked.push(i);
+                    true
+                }
+                _ => false,
+            };
+        }
+
+        for i in start..self.operands.len() {
+            picked.push(i);
+            if self.search(target ^ self.operands[i].value, i + 1, size - 1, picked) {
+                return true;
+            }
+            picked.pop();
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::Parser;
+
+    #[test]
+    fn explains_two_lost_operands() {
+        let config = Config::parse_from(["contest", "--threads=4", "--rounds=16", "--seed=7"]);
+        let log = OperandLog::replay(&config);
+        let (a, b) = (log.operands[3], log.operands[37]);
+
+        let diagnosis = log.explain(a.value ^ b.value, 3);
+        assert!(diagnosis.explained);
+        let lost: Vec<_> = diagnosis
+            .lost
+            .iter()
+            .map(|op| (op.thread, op.round))
+            .collect();
+        assert_eq!(lost, [(a.thread, a.round), (b.thread, b.round)]);
+    }
+
+    #[test]
+    fn pads_operands_to_the_width() {
+        let operand = |width| Operand {
+            thread: 1,
+            round: 2,
+            value: 0x5,
+            width,
+        };
+        assert_eq!(operand(Width::U8).to_string(), "thread 1 round 2 (0x05)");
+        assert_eq!(
+            operand(Width::U128).to_string(),
+            format!("thread 1 round 2 (0x{:032x})", 5)
+        );
+    }
+}
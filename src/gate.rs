@@ -0,0 +1,77 @@
+//! Holding the racing threads back until every one of them is
+//! ready. Otherwise the first threads spawned get a head start on
+//! a value nobody else is touching yet, and the race is less
+//! contended than it looks.
+
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+    fmt, hint,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Barrier,
+    },
+    time::Instant,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Gate {
+    /// Every thread starts as soon as it's spawned.
+    None,
+    /// A `std::sync::Barrier`. Waiting threads sleep, so it works
+    /// with any number of threads, but waking them takes a while.
+    Barrier,
+    /// Waiting threads spin on a counter, so they're let go within
+    /// a cache miss of each other. With more threads than CPUs,
+    /// the waiting can take whole scheduler time slices.
+    Spin,
+}
+
+impl fmt::Display for Gate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// One race's gate, shared by all of its threads.
+pub enum StartGate {
+    Open,
+    Barrier(Barrier),
+    Spin {
+        arrived: AtomicUsize,
+        threads: usize,
+    },
+}
+
+impl StartGate {
+    pub fn new(gate: Gate, threads: usize) -> Self {
+        match gate {
+            Gate::None => Self::Open,
+            Gate::Barrier => Self::Barrier(Barrier::new(threads)),
+            Gate::Spin => Self::Spin {
+                arrived: AtomicUsize::new(0),
+                threads,
+            },
+        }
+    }
+
+    /// Waits for every thread to get here, and returns when this
+    /// one was let go.
+    pub fn wait(&self) -> Instant {
+        match self {
+            Self::Open => {}
+            Self::Barrier(barrier) => {
+                barrier.wait();
+            }
+            Self::Spin { arrived, threads } => {
+                arrived.fetch_add(1, Ordering::AcqRel);
+                while arrived.load(Ordering::Acquire) < *threads {
+                    hint::spin_loop();
+                }
+            }
+        }
+        Instant::now()
+    }
+}
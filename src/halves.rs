@@ -0,0 +1,86 @@
+//! The `halves` module stores the value as two 32-bit halves
+//! that are loaded and stored one after the other, so a lost
+//! update can hit one half and not the other. Corruption
+//! confined to one half is what a torn 64-bit access looks like.
+
+use crate::{
+    layout::{Arena, Shared},
+    op::Operation,
+    race::Race,
+};
+use std::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicU32, Ordering},
+};
+
+fn split(value: u64) -> [u32; 2] {
+    [value as u32, (value >> 32) as u32]
+}
+
+fn join([low, high]: [u32; 2]) -> u64 {
+    (high as u64) << 32 | low as u64
+}
+
+/// Each half gets its own atomic load and store. No data races,
+/// but each half can lose updates independently.
+#[derive(Clone)]
+pub struct SharedHalves(Shared<[AtomicU32; 2]>);
+
+impl Race for SharedHalves {
+    type Word = u64;
+
+    fn new(arena: &Arena) -> Self {
+        Self(arena.place([AtomicU32::new(0), AtomicU32::new(0)]))
+    }
+
+    fn get(&self) -> u64 {
+        join(self.0.each_ref().map(|half| half.load(Ordering::Relaxed)))
+    }
+
+    fn address(&self) -> usize {
+        self.0.as_ptr() as usize
+    }
+
+    fn apply<O: Operation>(&self, operand: u64) {
+        let current = join(self.0.each_ref().map(|half| half.load(Ordering::Relaxed)));
+        for (half, bits) in self.0.iter().zip(split(O::apply(current, operand))) {
+            half.store(bits, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Two plain `u32`s behind an `UnsafeCell`, like `SharedUnsync`
+/// but split in half.
+#[derive(Clone)]
+pub struct SharedUnsyncHalves(Shared<UnsafeCell<[u32; 2]>>);
+
+impl Race for SharedUnsyncHalves {
+    type Word = u64;
+
+    fn new(arena: &Arena) -> Self {
+        Self(arena.place(UnsafeCell::new([0; 2])))
+    }
+
+    fn get(&self) -> u64 {
+        unsafe { join(*self.0.get()) }
+    }
+
+    fn address(&self) -> usize {
+        self.0.as_ptr() as usize
+    }
+
+    fn apply<O: Operation>(&self, operand: u64) {
+        let halves = self.0.get() as *mut u32;
+        // SAFETY: very unsafe, one half at a time.
+        unsafe {
+            let current = join([*halves, *halves.add(1)]);
+            let [low, high] = split(O::apply(current, operand));
+            *halves = low;
+            *halves.add(1) = high;
+        }
+    }
+}
+
+// SAFETY: still unsafe.
+unsafe impl Send for SharedUnsyncHalves {}
+unsafe impl Sync for SharedUnsyncHalves {}
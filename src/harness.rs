@@ -0,0 +1,302 @@
+//! The generic race harness. The hot loop is generic over the
+//! [`Lineup`] being raced: a contestant on its own gets a loop
+//! monomorphized for its [`Race`], and a lineup of several picked
+//! at runtime shares one that calls them through the registry's
+//! trait objects.
+
+use crate::{
+    affinity,
+    cli::{Config, Mode},
+    gate::StartGate,
+    layout::{Address, Arena},
+    race::Race,
+    timing::Timing,
+    word::{Width, Word},
+    workload::{self, Expectation, Steps, Workload},
+};
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+use serde::Serialize;
+use std::{
+    fmt, panic,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+/// What a single contestant looked like once the race was over.
+#[derive(Debug, Clone)]
+pub struct RaceOutcome {
+    pub value: u128,
+    /// The bits of `value` that are wrong. See [`Expectation::error`].
+    pub error: u128,
+    pub timing: Timing,
+    pub counters: Vec<Counter>,
+    /// Where the contestant's shared value was.
+    pub address: Address,
+}
+
+/// One of a contestant's extra counters, from every thread.
+#[derive(Debug, Clone, Serialize)]
+pub struct Counter {
+    pub name: &'static str,
+    pub threads: Vec<u64>,
+}
+
+impl Counter {
+    pub fn total(&self) -> u64 {
+        self.threads.iter().sum()
+    }
+}
+
+impl fmt::Display for Counter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let min = self.threads.iter().min().copied().unwrap_or_default();
+        let max = self.threads.iter().max().copied().unwrap_or_default();
+        write!(
+            f,
+            "{} {}, per thread {}..{}",
+            self.name,
+            self.total(),
+            min,
+            max
+        )
+    }
+}
+
+/// A set of contestants that share the same threads and the
+/// same random values. Operands come in as `u128` and each
+/// contestant keeps as many low bits as its [`Word`] has.
+pub trait Lineup: Clone + Send + 'static {
+    /// Every contestant does rep `rep` of `W` for `operand`, one
+    /// after another.
+    fn step<W: Steps>(&self, rep: usize, operand: u128);
+
+    /// Every contestant does all `reps` of `W` for `operand` before
+    /// the next one starts. The time each phase took is added to
+    /// the contestant's slot in `spent`.
+    fn phased<W: Steps>(&self, operand: u128, reps: usize, spent: &mut [Duration]);
+
+    fn values(&self) -> Vec<u128>;
+
+    /// Every contestant's [`Race::address`].
+    fn addresses(&self) -> Vec<usize>;
+
+    /// Every contestant's [`Race::counters`].
+    fn counters(&self) -> Vec<Vec<(&'static str, u64)>>;
+
+    fn count(&self) -> usize;
+}
+
+/// A contestant on its own, so its loop is monomorphized down to
+/// the [`Race`] itself.
+impl<R: Race> Lineup for (R,) {
+    fn step<W: Steps>(&self, rep: usize, operand: u128) {
+        W::step(&self.0, rep, Word::truncate(operand));
+    }
+
+    fn phased<W: Steps>(&self, operand: u128, reps: usize, spent: &mut [Duration]) {
+        let start = Instant::now();
+        W::phase(&self.0, Word::truncate(operand), reps);
+        spent[0] += start.elapsed();
+    }
+
+    fn values(&self) -> Vec<u128> {
+        vec![self.0.get().into()]
+    }
+
+    fn addresses(&self) -> Vec<usize> {
+        vec![self.0.address()]
+    }
+
+    fn counters(&self) -> Vec<Vec<(&'static str, u64)>> {
+        vec![self.0.counters()]
+    }
+
+    fn count(&self) -> usize {
+        1
+    }
+}
+
+/// The seed for thread `index`'s generator. Both halves go through
+/// SplitMix64 so neighbouring seeds and neighbouring threads don't
+/// end up with related streams.
+pub fn thread_seed(master: u64, index: usize) -> u64 {
+    splitmix64(master ^ splitmix64(index as u64))
+}
+
+/// The values thread `index` races with, one per round. Anything
+/// that needs to know what a thread did can replay them from here.
+/// Narrower widths keep the low bits of the same values `u64` gets.
+///
+/// Random values would leave nothing for the or workload to check,
+/// since enough of them or together to all ones. Instead every bit
+/// gets exactly one operand of its own, dealt out round by round
+/// across the threads, and every other operand is zero.
+pub fn operands(config: &Config, index: usize) -> impl Iterator<Item = u128> + Send + 'static {
+    let seed = config.seed.expect("resolved before racing");
+    let (threads, width) = (config.threads, config.width);
+    let sparse = config.workload == Workload::Or;
+    let mut rng = SmallRng::seed_from_u64(thread_seed(seed, index));
+    (0..config.rounds).map(move |round| {
+        if sparse {
+            let bit = round * threads + index;
+            return match bit < width.bits() as usize {
+                true => 1 << bit,
+                false => 0,
+            };
+        }
+        match width {
+            Width::U128 => rng.gen(),
+            _ => rng.gen::<u64>() as u128 & width.mask(),
+        }
+    })
+}
+
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Race a single contestant on its own, placed by `arena`.
+pub fn run_race<R: Race>(config: &Config, arena: &Arena) -> RaceOutcome {
+    run_lineup(config, (R::new(arena),)).remove(0)
+}
+
+/// Race every contestant in `lineup` on the same threads, returning
+/// one outcome per contestant in lineup order. [`Mode::Alternating`]
+/// runs them in phases and times each phase; anything else
+/// interleaves them, so every contestant gets the full time.
+pub fn run_lineup<L: Lineup>(config: &Config, lineup: L) -> Vec<RaceOutcome> {
+    match config.workload {
+        Workload::Xor => race_lineup::<L, workload::Xors>(config, lineup),
+        Workload::AddSub => race_lineup::<L, workload::AddSubs>(config, lineup),
+        Workload::Add => race_lineup::<L, workload::Adds>(config, lineup),
+        Workload::Or => race_lineup::<L, workload::Ors>(config, lineup),
+        Workload::OrAnd => race_lineup::<L, workload::OrAnds>(config, lineup),
+        Workload::Swap => race_lineup::<L, workload::Swaps>(config, lineup),
+    }
+}
+
+fn race_lineup<L: Lineup, W: Steps>(config: &Config, lineup: L) -> Vec<RaceOutcome> {
+    let (reps, mode) = (config.reps, config.mode);
+    let count = lineup.count();
+    let gate = Arc::new(StartGate::new(config.gate, config.threads));
+
+    let mut threads = Vec::new();
+    for index in 0..config.threads {
+        let lineup = lineup.clone();
+        let operands = operands(config, index);
+        let gate = gate.clone();
+        let cpu = config.cpus.as_ref().map(|cpus| cpus[index]);
+
+        let handle = std::thread::spawn(move || {
+            // Every thread has to get to the gate, even if it can't
+            // be pinned, or the rest would wait for it forever.
+            let pinned = cpu.map_or(Ok(()), affinity::pin);
+            let mut spent = vec![Duration::ZERO; count];
+            let start = gate.wait();
+            if let (Some(cpu), Err(e)) = (cpu, pinned) {
+                panic!("can't pin to CPU {cpu}: {e}");
+            }
+            for n in operands {
+                if mode == Mode::Alternating {
+                    lineup.phased::<W>(n, reps, &mut spent);
+                } else {
+                    for rep in 0..reps {
+                        lineup.step::<W>(rep, n);
+                    }
+                }
+            }
+            (start, start.elapsed(), spent, lineup.counters())
+        });
+
+        threads.push(handle);
+    }
+
+    let per_thread: Vec<_> = threads
+        .into_iter()
+        .map(|t| t.join().unwrap_or_else(|e| panic::resume_unwind(e)))
+        .collect();
+    // The race starts when the first thread gets going, and every
+    // other thread's skew is how far behind it started.
+    let first = per_thread.iter().map(|(start, ..)| *start).min();
+    let first = first.unwrap_or_else(Instant::now);
+    let wall = first.elapsed();
+    let start_skew: Vec<_> = per_thread
+        .iter()
+        .map(|(start, ..)| start.duration_since(first))
+        .collect();
+    let ops_per_thread = (config.rounds * reps) as u64;
+    let expectation = Expectation::new(config);
+
+    lineup
+        .values()
+        .into_iter()
+        .zip(lineup.addresses())
+        .enumerate()
+        .map(|(i, (value, address))| {
+            let timing = if mode == Mode::Alternating {
+                let threads: Vec<_> = per_thread.iter().map(|(_, _, spent, _)| spent[i]).collect();
+                Timing {
+                    wall: threads.iter().max().copied().unwrap_or_default(),
+                    threads,
+                    start_skew: start_skew.clone(),
+                    ops_per_thread,
+                }
+            } else {
+                Timing {
+                    wall,
+                    threads: per_thread.iter().map(|(_, total, ..)| *total).collect(),
+                    start_skew: start_skew.clone(),
+                    ops_per_thread,
+                }
+            };
+            let counters = gather_counters(per_thread.iter().map(|(.., c)| &c[i]));
+            RaceOutcome {
+                value,
+                error: expectation.error(value),
+                timing,
+                counters,
+                address: Address::new(address, config.line_size),
+            }
+        })
+        .collect()
+}
+
+/// Turns each thread's counters into one [`Counter`] per name.
+fn gather_counters<'a>(
+    threads: impl Iterator<Item = &'a Vec<(&'static str, u64)>>,
+) -> Vec<Counter> {
+    let mut counters: Vec<Counter> = Vec::new();
+    for thread in threads {
+        for &(name, value) in thread {
+            match counters.iter_mut().find(|c| c.name == name) {
+                Some(counter) => counter.threads.push(value),
+                None => counters.push(Counter {
+                    name,
+                    threads: vec![value],
+                }),
+            }
+        }
+    }
+    counters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{atomic::SharedAtomic, layout::Layout};
+    use clap::Parser;
+
+    /// A thread that can't be pinned still lets the others through
+    /// the gate, so the race fails instead of hanging.
+    #[test]
+    #[should_panic(expected = "can't pin")]
+    fn pinning_failures_dont_hang_the_gate() {
+        let mut config = Config::parse_from(["contest", "--threads=2", "--seed=1", "--gate=spin"]);
+        config.cpus = Some(vec![usize::MAX, 0]);
+        let arena = Arena::new(Layout::Heap, config.line_size);
+        run_race::<SharedAtomic>(&config, &arena);
+    }
+}
@@ -0,0 +1,226 @@
+//! What machine the race ran on. How many cores contend, whether
+//! they're hardware threads of the same core, and how big a cache
+//! line is all change what a result means.
+
+use crate::topology::{self, Topology};
+use serde::Serialize;
+use std::{env, fmt, fs, path::Path, sync::OnceLock, thread};
+
+const CACHE_DIR: &str = "/sys/devices/system/cpu/cpu0/cache";
+
+/// What's assumed when the line size can't be read.
+const DEFAULT_LINE_SIZE: usize = 64;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Host {
+    pub hostname: String,
+    pub os: &'static str,
+    pub arch: &'static str,
+    /// Hardware threads this process may run on.
+    pub cpus: usize,
+    /// `model name` from `/proc/cpuinfo`, where it has one.
+    pub model: Option<String>,
+    pub packages: usize,
+    /// Physical cores, across all packages.
+    pub cores: usize,
+    /// The most hardware threads any core has; more than one with
+    /// SMT.
+    pub threads_per_core: usize,
+    pub numa_nodes: usize,
+    /// The first CPU's caches, as the kernel lists them.
+    pub caches: Vec<Cache>,
+    /// Cache line size in bytes.
+    pub line_size: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Cache {
+    pub level: u8,
+    /// `Data`, `Instruction` or `Unified`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// In bytes.
+    pub size: usize,
+    pub line_size: usize,
+    /// How many hardware threads share it.
+    pub shared_by: usize,
+}
+
+impl Host {
+    /// The host this process is running on, detected the first
+    /// time it's asked for.
+    pub fn get() -> &'static Self {
+        static HOST: OnceLock<Host> = OnceLock::new();
+        HOST.get_or_init(Self::detect)
+    }
+
+    fn detect() -> Self {
+        let hostname = fs::read_to_string("/proc/sys/kernel/hostname")
+            .map(|s| s.trim().to_owned())
+            .or_else(|_| env::var("HOSTNAME"))
+            .unwrap_or_else(|_| "unknown".to_owned());
+
+        let topology = Topology::detect();
+        let mut cores: Vec<_> = topology.cpus.iter().map(|c| (c.package, c.core)).collect();
+        cores.sort_unstable();
+        let threads_per_core = cores
+            .chunk_by(|a, b| a == b)
+            .map(<[_]>::len)
+            .max()
+            .unwrap_or(1);
+        cores.dedup();
+        let mut packages: Vec<_> = cores.iter().map(|&(package, _)| package).collect();
+        packages.dedup();
+
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo").unwrap_or_default();
+        let caches = caches();
+        let line_size = caches
+            .iter()
+            .map(|c| c.line_size)
+            .find(|&size| size > 0)
+            .or_else(|| cpuinfo_field(&cpuinfo, "cache_alignment")?.parse().ok())
+            .unwrap_or(DEFAULT_LINE_SIZE);
+
+        Self {
+            hostname,
+            os: env::consts::OS,
+            arch: env::consts::ARCH,
+            cpus: thread::available_parallelism().map_or(1, |n| n.get()),
+            model: cpuinfo_field(&cpuinfo, "model name").map(str::to_owned),
+            packages: packages.len().max(1),
+            cores: cores.len().max(1),
+            threads_per_core,
+            numa_nodes: topology.nodes().len().max(1),
+            caches,
+            line_size,
+        }
+    }
+
+    /// One thread per CPU this process may run on, but at least
+    /// two, so there's always something to race against.
+    pub fn default_threads(&self) -> usize {
+        self.cpus.max(2)
+    }
+}
+
+impl fmt::Display for Cache {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let suffix = match self.kind.as_str() {
+            "Data" => "d",
+            "Instruction" => "i",
+            _ => "",
+        };
+        write!(f, "L{}{suffix} ", self.level)?;
+        match self.size {
+            size if size >= 1 << 20 && size.is_multiple_of(1 << 20) => {
+                write!(f, "{} MiB", size >> 20)
+            }
+            size if size >= 1 << 10 => write!(f, "{} KiB", size >> 10),
+            size => write!(f, "{size} B"),
+        }
+    }
+}
+
+/// `cpu0`'s caches from sysfs, in the order the kernel lists them.
+fn caches() -> Vec<Cache> {
+    let mut indices: Vec<usize> = fs::read_dir(CACHE_DIR)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name();
+            name.to_str()?.strip_prefix("index")?.parse().ok()
+        })
+        .collect();
+    indices.sort_unstable();
+
+    indices
+        .into_iter()
+        .filter_map(|index| {
+            let dir = Path::new(CACHE_DIR).join(format!("index{index}"));
+            let read = |file: &str| {
+                fs::read_to_string(dir.join(file))
+                    .ok()
+                    .map(|s| s.trim().to_owned())
+            };
+            Some(Cache {
+                level: read("level")?.parse().ok()?,
+                kind: read("type")?,
+                size: parse_size(&read("size")?)?,
+                line_size: read("coherency_line_size")
+                    .and_then(|s| s.parse().ok())
+                    .unwrap_or(0),
+                shared_by: read("shared_cpu_list")
+                    .and_then(|s| topology::parse_list(&s))
+                    .map_or(1, |cpus| cpus.len()),
+            })
+        })
+        .collect()
+}
+
+/// Parses a sysfs size like `48K`.
+fn parse_size(s: &str) -> Option<usize> {
+    let (digits, shift) = match s.as_bytes().last()? {
+        b'K' => (&s[..s.len() - 1], 10),
+        b'M' => (&s[..s.len() - 1], 20),
+        b'G' => (&s[..s.len() - 1], 30),
+        _ => (s, 0),
+    };
+    Some(digits.parse::<usize>().ok()? << shift)
+}
+
+/// The value of the first `key : value` line in `/proc/cpuinfo`
+/// with this key.
+fn cpuinfo_field<'a>(cpuinfo: &'a str, key: &str) -> Option<&'a str> {
+    cpuinfo.lines().find_map(|line| {
+        let (k, v) = line.split_once(':')?;
+        (k.trim() == key).then(|| v.trim())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_sysfs_sizes() {
+        assert_eq!(parse_size("48K"), Some(48 << 10));
+        assert_eq!(parse_size("307200K"), Some(300 << 20));
+        assert_eq!(parse_size("16M"), Some(16 << 20));
+        assert_eq!(parse_size("1G"), Some(1 << 30));
+        assert_eq!(parse_size("512"), Some(512));
+    }
+
+    #[test]
+    fn rejects_malformed_sizes() {
+        assert_eq!(parse_size(""), None);
+        assert_eq!(parse_size("K"), None);
+        assert_eq!(parse_size("12T"), None);
+        assert_eq!(parse_size("-4K"), None);
+    }
+
+    #[test]
+    fn finds_cpuinfo_fields() {
+        let cpuinfo = "processor\t: 0\nmodel name\t: Some CPU @ 3.00GHz\ncache_alignment\t: 64\n";
+        assert_eq!(
+            cpuinfo_field(cpuinfo, "model name"),
+            Some("Some CPU @ 3.00GHz")
+        );
+        assert_eq!(cpuinfo_field(cpuinfo, "cache_alignment"), Some("64"));
+        assert_eq!(cpuinfo_field(cpuinfo, "model"), None);
+    }
+
+    #[test]
+    fn shows_cache_sizes_in_binary_units() {
+        let cache = |kind: &str, level, size| Cache {
+            level,
+            kind: kind.to_owned(),
+            size,
+            line_size: 64,
+            shared_by: 1,
+        };
+        assert_eq!(cache("Data", 1, 48 << 10).to_string(), "L1d 48 KiB");
+        assert_eq!(cache("Instruction", 1, 32 << 10).to_string(), "L1i 32 KiB");
+        assert_eq!(cache("Unified", 3, 300 << 20).to_string(), "L3 300 MiB");
+        assert_eq!(cache("Unified", 2, 1536 << 10).to_string(), "L2 1536 KiB");
+    }
+}
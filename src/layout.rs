@@ -0,0 +1,233 @@
+//! Where the contestants' shared values live in memory. Left to
+//! the allocator, whether two contestants share a cache line is
+//! an accident, and false sharing between them can look like
+//! one contestant slowing the other down. An [`Arena`] places
+//! every value a lineup allocates according to a [`Layout`].
+
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+    alloc::{self, Layout as AllocLayout},
+    cell::Cell,
+    fmt, mem,
+    ops::Deref,
+    ptr::NonNull,
+    sync::{Arc, Mutex},
+};
+
+/// Assumed page size.
+pub const PAGE: usize = 4096;
+
+/// How much room a placed lineup gets. Enough for every
+/// contestant on a page of its own.
+const ARENA: usize = 64 * PAGE;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// Every value gets its own allocation, wherever the
+    /// allocator puts it.
+    Heap,
+    /// Values are packed next to each other, so small ones share
+    /// a cache line.
+    SameLine,
+    /// Every value starts on a cache line of its own, right after
+    /// the previous one.
+    AdjacentLines,
+    /// Every value starts on a page of its own.
+    Pages,
+    /// Every value is aligned to two cache lines, like
+    /// `#[repr(align(128))]` with 64-byte lines, so neither its line
+    /// nor the adjacent-line prefetcher's partner line is shared.
+    Padded,
+}
+
+impl Layout {
+    /// What every value's offset is rounded up to, on top of its
+    /// own alignment, with cache lines `line` bytes long.
+    fn boundary(self, line: usize) -> usize {
+        match self {
+            Self::Heap | Self::SameLine => 1,
+            Self::AdjacentLines => line,
+            Self::Pages => PAGE,
+            Self::Padded => 2 * line,
+        }
+    }
+}
+
+/// A shared value's address, and where that puts it in terms of
+/// cache lines and pages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Address {
+    pub address: usize,
+    /// The address divided by the line size.
+    pub line: usize,
+    pub page_offset: usize,
+}
+
+impl Address {
+    pub fn new(address: usize, line_size: usize) -> Self {
+        Self {
+            address,
+            line: address / line_size,
+            page_offset: address % PAGE,
+        }
+    }
+}
+
+impl fmt::Display for Address {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:#x}, line {:#x}, page offset {:#05x}",
+            self.address, self.line, self.page_offset
+        )
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// Where something was placed, and a type-erased `drop_in_place`
+/// for it.
+type Placed = (usize, unsafe fn(*mut u8));
+
+/// One allocation, and how to drop whatever was placed in it.
+struct Block {
+    base: NonNull<u8>,
+    layout: AllocLayout,
+    drops: Mutex<Vec<Placed>>,
+}
+
+// SAFETY: the block only hands out `Shared`s, which carry the
+// `Send` and `Sync` requirements of what's in them.
+unsafe impl Send for Block {}
+unsafe impl Sync for Block {}
+
+impl Block {
+    fn new(layout: AllocLayout) -> Self {
+        assert_ne!(layout.size(), 0, "nothing to place");
+        // SAFETY: the size isn't zero.
+        let base = unsafe { alloc::alloc_zeroed(layout) };
+        Self {
+            base: NonNull::new(base).unwrap_or_else(|| alloc::handle_alloc_error(layout)),
+            layout,
+            drops: Mutex::default(),
+        }
+    }
+
+    /// Moves `value` to `offset`, which has to be in bounds and
+    /// suitably aligned, and leaves nothing else there.
+    fn put<T>(self: &Arc<Self>, offset: usize, value: T) -> Shared<T> {
+        /// Type-erased `drop_in_place`.
+        unsafe fn drop<T>(ptr: *mut u8) {
+            ptr.cast::<T>().drop_in_place();
+        }
+
+        // SAFETY: in bounds, aligned, and not in use, as promised.
+        let ptr = unsafe {
+            let ptr = self.base.as_ptr().add(offset).cast::<T>();
+            ptr.write(value);
+            NonNull::new_unchecked(ptr)
+        };
+        if mem::needs_drop::<T>() {
+            self.drops.lock().unwrap().push((offset, drop::<T>));
+        }
+        Shared {
+            ptr,
+            block: self.clone(),
+        }
+    }
+}
+
+impl Drop for Block {
+    fn drop(&mut self) {
+        for &(offset, drop) in self.drops.get_mut().unwrap().iter() {
+            // SAFETY: something of the right type was put there,
+            // and every `Shared` pointing at it is gone.
+            unsafe { drop(self.base.as_ptr().add(offset)) };
+        }
+        // SAFETY: allocated in `new` with the same layout.
+        unsafe { alloc::dealloc(self.base.as_ptr(), self.layout) };
+    }
+}
+
+/// A value placed by an [`Arena`]. Cloning it shares the value,
+/// like an `Arc`.
+pub struct Shared<T> {
+    ptr: NonNull<T>,
+    block: Arc<Block>,
+}
+
+impl<T> Shared<T> {
+    pub fn as_ptr(&self) -> *const T {
+        self.ptr.as_ptr()
+    }
+}
+
+impl<T> Clone for Shared<T> {
+    fn clone(&self) -> Self {
+        Self {
+            ptr: self.ptr,
+            block: self.block.clone(),
+        }
+    }
+}
+
+impl<T> Deref for Shared<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: the block, and so the value, lives as long as we do.
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+// SAFETY: the same rules as `Arc`.
+unsafe impl<T: Send + Sync> Send for Shared<T> {}
+unsafe impl<T: Send + Sync> Sync for Shared<T> {}
+
+/// Places the shared values of one lineup, in the order they're
+/// asked for.
+pub struct Arena {
+    layout: Layout,
+    line_size: usize,
+    block: Option<Arc<Block>>,
+    next: Cell<usize>,
+}
+
+impl Arena {
+    pub fn new(layout: Layout, line_size: usize) -> Self {
+        let block = (layout != Layout::Heap).then(|| {
+            let layout = AllocLayout::from_size_align(ARENA, PAGE).expect("valid layout");
+            Arc::new(Block::new(layout))
+        });
+        Self {
+            layout,
+            line_size,
+            block,
+            next: Cell::new(0),
+        }
+    }
+
+    pub fn place<T>(&self, value: T) -> Shared<T> {
+        let Some(block) = &self.block else {
+            return Arc::new(Block::new(AllocLayout::new::<T>())).put(0, value);
+        };
+
+        let align = mem::align_of::<T>().max(self.layout.boundary(self.line_size));
+        let offset = self.next.get().next_multiple_of(align);
+        let end = offset + mem::size_of::<T>();
+        assert!(
+            end <= block.layout.size(),
+            "the {} layout ran out of room",
+            self.layout
+        );
+        self.next.set(end);
+        block.put(offset, value)
+    }
+}
@@ -0,0 +1,183 @@
+//! The `locks` module keeps the value behind a lock, which is
+//! always correct but serializes every update. These are the
+//! baselines for what correctness costs without atomics.
+
+use crate::{
+    layout::{Arena, Shared},
+    op::Operation,
+    race::{AnyWidth, Race},
+    word::Word,
+};
+use std::{
+    cell::UnsafeCell,
+    hint,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex, RwLock,
+    },
+};
+
+#[derive(Clone)]
+pub struct SharedMutex<W = u64>(Shared<Mutex<W>>);
+
+impl<W: Word> Race for SharedMutex<W> {
+    type Word = W;
+
+    fn new(arena: &Arena) -> Self {
+        Self(arena.place(Mutex::default()))
+    }
+
+    fn get(&self) -> W {
+        *self.0.lock().unwrap()
+    }
+
+    fn address(&self) -> usize {
+        self.0.as_ptr() as usize
+    }
+
+    fn apply<O: Operation>(&self, operand: W) {
+        let mut value = self.0.lock().unwrap();
+        *value = O::apply(*value, operand);
+    }
+}
+
+impl<W: Word> AnyWidth for SharedMutex<W> {
+    type At<V: Word> = SharedMutex<V>;
+}
+
+/// Reads take the shared lock. Every update still needs the
+/// exclusive one, so this mostly measures `RwLock`'s overhead
+/// over `Mutex`.
+#[derive(Clone)]
+pub struct SharedRwLock<W = u64>(Shared<RwLock<W>>);
+
+impl<W: Word> Race for SharedRwLock<W> {
+    type Word = W;
+
+    fn new(arena: &Arena) -> Self {
+        Self(arena.place(RwLock::default()))
+    }
+
+    fn get(&self) -> W {
+        *self.0.read().unwrap()
+    }
+
+    fn address(&self) -> usize {
+        self.0.as_ptr() as usize
+    }
+
+    fn apply<O: Operation>(&self, operand: W) {
+        let mut value = self.0.write().unwrap();
+        *value = O::apply(*value, operand);
+    }
+}
+
+impl<W: Word> AnyWidth for SharedRwLock<W> {
+    type At<V: Word> = SharedRwLock<V>;
+}
+
+/// A hand-written spinlock. Waiters only ever spin, never park,
+/// so these get very slow once there are more threads than CPUs.
+pub trait RawSpinLock: Default + Send + Sync + 'static {
+    /// Whatever `unlock` needs to know about the acquisition.
+    type Guard;
+
+    fn lock(&self) -> Self::Guard;
+    fn unlock(&self, guard: Self::Guard);
+}
+
+/// Test-and-set: everyone hammers the same flag with swaps.
+#[derive(Default)]
+pub struct TestAndSet(AtomicBool);
+
+impl RawSpinLock for TestAndSet {
+    type Guard = ();
+
+    fn lock(&self) {
+        while self.0.swap(true, Ordering::Acquire) {
+            hint::spin_loop();
+        }
+    }
+
+    fn unlock(&self, _: ()) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+/// Ticket lock: waiters are served first come, first served.
+#[derive(Default)]
+pub struct Ticket {
+    next: AtomicUsize,
+    serving: AtomicUsize,
+}
+
+impl RawSpinLock for Ticket {
+    type Guard = usize;
+
+    fn lock(&self) -> usize {
+        let ticket = self.next.fetch_add(1, Ordering::Relaxed);
+        while self.serving.load(Ordering::Acquire) != ticket {
+            hint::spin_loop();
+        }
+        ticket
+    }
+
+    fn unlock(&self, ticket: usize) {
+        self.serving
+            .store(ticket.wrapping_add(1), Ordering::Release);
+    }
+}
+
+struct Spin<L, W> {
+    lock: L,
+    value: UnsafeCell<W>,
+}
+
+// SAFETY: `value` is only touched while `lock` is held.
+unsafe impl<L: Sync, W: Send> Sync for Spin<L, W> {}
+
+pub struct SharedSpin<L, W = u64>(Shared<Spin<L, W>>);
+
+// Deriving would needlessly require `L: Clone`.
+impl<L, W> Clone for SharedSpin<L, W> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<L: RawSpinLock, W: Word> SharedSpin<L, W> {
+    fn with<T>(&self, f: impl FnOnce(&mut W) -> T) -> T {
+        let guard = self.0.lock.lock();
+        // SAFETY: we hold the lock until after `f` returns.
+        let out = f(unsafe { &mut *self.0.value.get() });
+        self.0.lock.unlock(guard);
+        out
+    }
+}
+
+impl<L: RawSpinLock, W: Word> Race for SharedSpin<L, W> {
+    type Word = W;
+
+    fn new(arena: &Arena) -> Self {
+        Self(arena.place(Spin {
+            lock: L::default(),
+            value: UnsafeCell::new(W::default()),
+        }))
+    }
+
+    fn get(&self) -> W {
+        self.with(|value| *value)
+    }
+
+    fn address(&self) -> usize {
+        self.0.as_ptr() as usize
+    }
+
+    fn apply<O: Operation>(&self, operand: W) {
+        self.with(|value| *value = O::apply(*value, operand))
+    }
+}
+
+impl<L: RawSpinLock, W: Word> AnyWidth for SharedSpin<L, W> {
+    type At<V: Word> = SharedSpin<L, V>;
+}
@@ -1,105 +1,152 @@
-use crate::{atomic::SharedAtomic, unsync::SharedUnsync};
-use rand::{rngs::SmallRng, Rng, SeedableRng};
-
-fn main() {
-    let unsync = SharedUnsync::new();
-    let atomic = SharedAtomic::new();
-
-    let start = std::time::Instant::now();
-
-    let mut threads = Vec::new();
-    for _ in 0..32 {
-        let unsync = unsync.clone();
-        let atomic = atomic.clone();
-
-        let handle = std::thread::spawn(move || {
-            let mut rng = SmallRng::from_entropy();
-            for _ in 0..256 {
-                let n: u64 = rng.gen();
-                for _ in 0..2048 {
-                    do_xors(n, &atomic, &unsync);
-                }
-            }
+use crate::{
+    cli::{Config, Format, Mode},
+    diagnose::OperandLog,
+    harness::RaceOutcome,
+    layout::{Arena, Layout},
+    registry::Entry,
+    report::{ContestantReport, Report},
+    topology::Topology,
+    workload::Workload,
+};
+use clap::{error::ErrorKind, CommandFactory, Parser};
+use std::{io, time::Instant};
+
+mod affinity;
+mod asm;
+mod atomic;
+mod cas;
+mod cli;
+mod diagnose;
+mod gate;
+mod halves;
+mod harness;
+mod host;
+mod layout;
+mod locks;
+mod op;
+mod race;
+mod registry;
+mod report;
+mod sharded;
+mod stats;
+mod timing;
+mod topology;
+mod unsync;
+mod word;
+mod workload;
+
+fn main() -> io::Result<()> {
+    let mut config = Config::parse();
+    if config.diagnose.is_some() && config.workload != Workload::Xor {
+        Config::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "--diagnose only works with the xor workload",
+            )
+            .exit();
+    }
+    config.seed.get_or_insert_with(rand::random);
+    config.cpus = config
+        .pin
+        .plan(config.threads, &Topology::detect())
+        .unwrap_or_else(|e| {
+            Config::command()
+                .error(
+                    ErrorKind::ValueValidation,
+                    format!("can't --pin {}: {e}", config.pin),
+                )
+                .exit()
         });
 
-        threads.push(handle);
+    let entries: Vec<_> = config
+        .contestants
+        .iter()
+        .map(|name| registry::lookup(name).expect("validated by the CLI"))
+        .collect();
+    if let Some(entry) = entries.iter().find(|e| !e.supports(config.width)) {
+        let only = entry.width.expect("supports every width otherwise");
+        Config::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                format!("{} only races at --width {only}", entry.name),
+            )
+            .exit();
     }
 
-    threads.into_iter().for_each(|t| t.join().unwrap());
-
-    println!("unsync: {:064b}", unsync.get());
-    println!("atomic: {:064b}", atomic.get());
-    println!("took {:.0?}", start.elapsed());
+    let start = Instant::now();
+    let runs: Vec<_> = config
+        .layouts
+        .iter()
+        .map(|&layout| {
+            let trials: Vec<_> = (0..config.trials)
+                .map(|_| run(&config, layout, &entries))
+                .collect();
+            (layout, trials)
+        })
+        .collect();
+    let elapsed = start.elapsed();
+
+    let log = config
+        .diagnose
+        .map(|max_lost| (OperandLog::replay(&config), max_lost.into()));
+    let contestants = runs
+        .iter()
+        .flat_map(|(layout, trials)| {
+            entries.iter().enumerate().map(|(i, entry)| {
+                let outcomes: Vec<_> = trials.iter().map(|t| &t[i]).collect();
+                ContestantReport::new(entry.name, *layout, config.width, &outcomes, log.as_ref())
+            })
+        })
+        .collect();
+
+    let report = Report::new(&config, elapsed, contestants);
+    let stdout = io::stdout().lock();
+    match config.format {
+        Format::Text => report.write_text(stdout),
+        Format::Json => report.write_json(stdout),
+        Format::Csv => report.write_csv(stdout),
+    }
 }
 
-// Try commenting out `atomic.fetch_xor(n);`.
-fn do_xors(n: u64, atomic: &SharedAtomic, unsync: &SharedUnsync) {
-    atomic.fetch_xor(n);
-    unsync.fetch_xor(n);
-}
+/// Race the selected contestants. In interleaved mode each one's
+/// timing is what it adds on top of the rest of the lineup, found
+/// by racing the lineup again without it. The baselines go
+/// through the same dynamically dispatched lineup as the full
+/// race, even when only one contestant is left, so the difference
+/// is only the contestant.
+fn run(config: &Config, layout: Layout, entries: &[&Entry]) -> Vec<RaceOutcome> {
+    let mut outcomes = race(config, layout, entries);
+    if config.mode != Mode::Interleaved || entries.len() < 2 {
+        return outcomes;
+    }
 
-/// In order to participate in our race, you must provide
-/// methods to create yourself, perform xors, and inspect
-/// your value at the end to check against 0.
-trait Race: Clone + Send + Sync {
-    fn new() -> Self;
-    fn get(&self) -> u64;
-    fn fetch_xor(&self, other: u64);
+    for (i, outcome) in outcomes.iter_mut().enumerate() {
+        let mut rest = entries.to_vec();
+        rest.remove(i);
+        let baseline = lineup(config, layout, &rest);
+        outcome.timing = outcome.timing.saturating_sub(&baseline[0].timing);
+    }
+    outcomes
 }
 
-/// This module contains a type that erroneously implements
-/// Send and Sync without actually synchronising data access.
-/// Let's see what happens.
-mod unsync {
-    use super::Race;
-    use std::{cell::UnsafeCell, sync::Arc};
-
-    #[derive(Clone)]
-    pub struct SharedUnsync(Arc<UnsafeCell<u64>>);
-
-    impl Race for SharedUnsync {
-        fn new() -> Self {
-            Self(Arc::new(UnsafeCell::new(0)))
-        }
-
-        fn get(&self) -> u64 {
-            unsafe { *self.0.get() }
-        }
-
-        fn fetch_xor(&self, other: u64) {
-            // SAFETY: very unsafe.
-            unsafe { *self.0.get() ^= other }
-        }
+fn race(config: &Config, layout: Layout, entries: &[&Entry]) -> Vec<RaceOutcome> {
+    match (config.mode, entries) {
+        (Mode::Isolated, _) => entries
+            .iter()
+            .map(|e| (e.run)(config, &Arena::new(layout, config.line_size)))
+            .collect(),
+        (_, [entry]) => vec![(entry.run)(config, &Arena::new(layout, config.line_size))],
+        (_, _) => lineup(config, layout, entries),
     }
-
-    // SAFETY: still unsafe.
-    unsafe impl Send for SharedUnsync {}
-    unsafe impl Sync for SharedUnsync {}
 }
 
-/// The `atomic` module uses processor-intrinsics to do
-/// fetch-add atomically.
-mod atomic {
-    use super::Race;
-    use std::sync::{
-        atomic::{AtomicU64, Ordering},
-        Arc,
-    };
-
-    #[derive(Clone)]
-    pub struct SharedAtomic(Arc<AtomicU64>);
-
-    impl Race for SharedAtomic {
-        fn new() -> Self {
-            Self(Arc::new(AtomicU64::new(0)))
-        }
-
-        fn get(&self) -> u64 {
-            self.0.load(Ordering::Relaxed)
-        }
-
-        fn fetch_xor(&self, other: u64) {
-            self.0.fetch_xor(other, Ordering::Relaxed);
-        }
-    }
+/// Race `entries` together through the registry's dynamically
+/// dispatched lineup, whatever they are.
+fn lineup(config: &Config, layout: Layout, entries: &[&Entry]) -> Vec<RaceOutcome> {
+    let arena = Arena::new(layout, config.line_size);
+    let lineup: Vec<_> = entries
+        .iter()
+        .map(|e| (e.build)(config.width, &arena))
+        .collect();
+    harness::run_lineup(config, lineup)
 }
@@ -0,0 +1,92 @@
+//! The read-modify-write operations a contestant can be raced
+//! on. Each one is a type so the hot loop is monomorphized over
+//! it, and its [`OpKind`] lets atomics pick the matching native
+//! instruction at compile time.
+
+use crate::word::{AtomicWord, Word};
+use std::sync::atomic::Ordering;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpKind {
+    Xor,
+    Add,
+    Sub,
+    Or,
+    And,
+    Swap,
+}
+
+pub trait Operation: 'static {
+    const KIND: OpKind;
+
+    /// What the value becomes when `operand` is applied to
+    /// `current`.
+    fn apply<W: Word>(current: W, operand: W) -> W;
+}
+
+pub struct Xor;
+pub struct Add;
+pub struct Sub;
+pub struct Or;
+pub struct And;
+pub struct Swap;
+
+impl Operation for Xor {
+    const KIND: OpKind = OpKind::Xor;
+
+    fn apply<W: Word>(current: W, operand: W) -> W {
+        current ^ operand
+    }
+}
+
+impl Operation for Add {
+    const KIND: OpKind = OpKind::Add;
+
+    fn apply<W: Word>(current: W, operand: W) -> W {
+        current.wrapping_add(operand)
+    }
+}
+
+impl Operation for Sub {
+    const KIND: OpKind = OpKind::Sub;
+
+    fn apply<W: Word>(current: W, operand: W) -> W {
+        current.wrapping_sub(operand)
+    }
+}
+
+impl Operation for Or {
+    const KIND: OpKind = OpKind::Or;
+
+    fn apply<W: Word>(current: W, operand: W) -> W {
+        current | operand
+    }
+}
+
+impl Operation for And {
+    const KIND: OpKind = OpKind::And;
+
+    fn apply<W: Word>(current: W, operand: W) -> W {
+        current & operand
+    }
+}
+
+impl Operation for Swap {
+    const KIND: OpKind = OpKind::Swap;
+
+    fn apply<W: Word>(_: W, operand: W) -> W {
+        operand
+    }
+}
+
+/// Applies `O` with the atomic's own read-modify-write instruction.
+pub fn atomic_rmw<O: Operation, W: Word>(atomic: &W::Atomic, operand: W, ordering: Ordering) {
+    match O::KIND {
+        OpKind::Xor => atomic.fetch_xor(operand, ordering),
+        OpKind::Add => atomic.fetch_add(operand, ordering),
+        OpKind::Sub => atomic.fetch_sub(operand, ordering),
+        OpKind::Or => atomic.fetch_or(operand, ordering),
+        OpKind::And => atomic.fetch_and(operand, ordering),
+        OpKind::Swap => atomic.swap(operand, ordering),
+    };
+}
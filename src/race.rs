@@ -0,0 +1,40 @@
+use crate::{layout::Arena, op::Operation, word::Word};
+
+/// In order to participate in our race, you must provide
+/// methods to create yourself, apply read-modify-write
+/// operations, and inspect your value at the end to check it
+/// against what it should be.
+///
+/// Clones share the value being raced, and every thread races
+/// on its own clone, so anything a clone keeps to itself is
+/// per-thread.
+pub trait Race: Clone + Send + Sync + 'static {
+    /// The integer the value is raced as.
+    type Word: Word;
+
+    /// Starts at zero, with the shared value placed by `arena`.
+    fn new(arena: &Arena) -> Self;
+    fn get(&self) -> Self::Word;
+
+    /// Where the shared value lives, so the report can tell which
+    /// contestants ended up on the same cache line.
+    fn address(&self) -> usize;
+
+    /// Replaces the value with `O::apply(value, operand)`, as
+    /// atomically as the contestant manages.
+    fn apply<O: Operation>(&self, operand: Self::Word);
+
+    /// Extra counters worth reporting, like retries. Called on
+    /// each thread's clone once that thread is done racing.
+    fn counters(&self) -> Vec<(&'static str, u64)> {
+        Vec::new()
+    }
+}
+
+/// A [`Race`] that works at every [`Width`], as long as someone
+/// picks the [`Word`].
+///
+/// [`Width`]: crate::word::Width
+pub trait AnyWidth: Race {
+    type At<W: Word>: Race<Word = W>;
+}
@@ -0,0 +1,232 @@
+//! Every contestant that can be picked from the command line,
+//! keyed by name.
+
+#[cfg(target_arch = "x86_64")]
+use crate::asm::SharedAsmXorMem;
+use crate::{
+    asm::SharedAsm,
+    atomic::{self, SharedAtomic, SharedLoadStore},
+    cas::SharedCas,
+    cli::Config,
+    halves::{SharedHalves, SharedUnsyncHalves},
+    harness::{self, Lineup, RaceOutcome},
+    layout::Arena,
+    locks::{self, SharedMutex, SharedRwLock, SharedSpin},
+    race::{AnyWidth, Race},
+    sharded::SharedSharded,
+    unsync::{SharedUnsync, SharedVolatile},
+    word::{Width, Word},
+    workload::{self, Steps, Workload},
+};
+use clap::builder::PossibleValuesParser;
+use std::time::{Duration, Instant};
+
+/// An object-safe view of a [`Race`], so contestants chosen at
+/// runtime can share one hot loop. Values are widened to `u128`
+/// and operands truncated to the contestant's [`Word`].
+pub trait Contestant: Send + Sync {
+    fn get(&self) -> u128;
+    fn address(&self) -> usize;
+    fn step(&self, workload: Workload, rep: usize, operand: u128);
+    fn phase(&self, workload: Workload, operand: u128, reps: usize);
+    fn counters(&self) -> Vec<(&'static str, u64)>;
+    fn boxed_clone(&self) -> Box<dyn Contestant>;
+}
+
+impl<R: Race> Contestant for R {
+    fn get(&self) -> u128 {
+        Race::get(self).into()
+    }
+
+    fn address(&self) -> usize {
+        Race::address(self)
+    }
+
+    fn step(&self, workload: Workload, rep: usize, operand: u128) {
+        let operand = R::Word::truncate(operand);
+        match workload {
+            Workload::Xor => workload::Xors::step(self, rep, operand),
+            Workload::AddSub => workload::AddSubs::step(self, rep, operand),
+            Workload::Add => workload::Adds::step(self, rep, operand),
+            Workload::Or => workload::Ors::step(self, rep, operand),
+            Workload::OrAnd => workload::OrAnds::step(self, rep, operand),
+            Workload::Swap => workload::Swaps::step(self, rep, operand),
+        }
+    }
+
+    fn phase(&self, workload: Workload, operand: u128, reps: usize) {
+        let operand = R::Word::truncate(operand);
+        match workload {
+            Workload::Xor => workload::Xors::phase(self, operand, reps),
+            Workload::AddSub => workload::AddSubs::phase(self, operand, reps),
+            Workload::Add => workload::Adds::phase(self, operand, reps),
+            Workload::Or => workload::Ors::phase(self, operand, reps),
+            Workload::OrAnd => workload::OrAnds::phase(self, operand, reps),
+            Workload::Swap => workload::Swaps::phase(self, operand, reps),
+        }
+    }
+
+    fn counters(&self) -> Vec<(&'static str, u64)> {
+        Race::counters(self)
+    }
+
+    fn boxed_clone(&self) -> Box<dyn Contestant> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Contestant> {
+    fn clone(&self) -> Self {
+        self.boxed_clone()
+    }
+}
+
+/// Several contestants picked at runtime race through dynamic
+/// dispatch. A contestant on its own goes through [`Entry::run`],
+/// which is monomorphized for it.
+impl Lineup for Vec<Box<dyn Contestant>> {
+    fn step<W: Steps>(&self, rep: usize, operand: u128) {
+        for contestant in self.iter() {
+            contestant.step(W::WORKLOAD, rep, operand);
+        }
+    }
+
+    fn phased<W: Steps>(&self, operand: u128, reps: usize, spent: &mut [Duration]) {
+        for (contestant, spent) in self.iter().zip(spent) {
+            let start = Instant::now();
+            contestant.phase(W::WORKLOAD, operand, reps);
+            *spent += start.elapsed();
+        }
+    }
+
+    fn values(&self) -> Vec<u128> {
+        self.iter().map(|c| c.get()).collect()
+    }
+
+    fn addresses(&self) -> Vec<usize> {
+        self.iter().map(|c| c.address()).collect()
+    }
+
+    fn counters(&self) -> Vec<Vec<(&'static str, u64)>> {
+        self.iter().map(|c| c.counters()).collect()
+    }
+
+    fn count(&self) -> usize {
+        self.len()
+    }
+}
+
+pub struct Entry {
+    pub name: &'static str,
+    /// The only width this contestant races at, if it can't do
+    /// them all.
+    pub width: Option<Width>,
+    pub build: fn(Width, &Arena) -> Box<dyn Contestant>,
+    /// Races the contestant on its own, at `config.width`.
+    pub run: fn(&Config, &Arena) -> RaceOutcome,
+}
+
+impl Entry {
+    /// A contestant that races at every width.
+    const fn of<R: AnyWidth>(name: &'static str) -> Self {
+        Self {
+            name,
+            width: None,
+            build: |width, arena| match width {
+                Width::U8 => Box::new(<R::At<u8>>::new(arena)),
+                Width::U16 => Box::new(<R::At<u16>>::new(arena)),
+                Width::U32 => Box::new(<R::At<u32>>::new(arena)),
+                Width::U64 => Box::new(<R::At<u64>>::new(arena)),
+                Width::U128 => Box::new(<R::At<u128>>::new(arena)),
+            },
+            run: |config, arena| match config.width {
+                Width::U8 => harness::run_race::<R::At<u8>>(config, arena),
+                Width::U16 => harness::run_race::<R::At<u16>>(config, arena),
+                Width::U32 => harness::run_race::<R::At<u32>>(config, arena),
+                Width::U64 => harness::run_race::<R::At<u64>>(config, arena),
+                Width::U128 => harness::run_race::<R::At<u128>>(config, arena),
+            },
+        }
+    }
+
+    /// A contestant that only races at its own [`Race::Word`].
+    const fn only<R: Race>(name: &'static str) -> Self {
+        Self {
+            name,
+            width: Some(R::Word::WIDTH),
+            build: |_, arena| Box::new(R::new(arena)),
+            run: harness::run_race::<R>,
+        }
+    }
+
+    pub fn supports(&self, width: Width) -> bool {
+        self.width.is_none_or(|only| only == width)
+    }
+}
+
+pub const CONTESTANTS: &[Entry] = &[
+    Entry::of::<SharedUnsync>("unsync"),
+    Entry::of::<SharedVolatile>("unsync-volatile"),
+    Entry::only::<SharedAsm>("asm"),
+    #[cfg(target_arch = "x86_64")]
+    Entry::only::<SharedAsmXorMem>("asm-xor-mem"),
+    Entry::of::<SharedAtomic>("atomic"),
+    Entry::of::<SharedAtomic<atomic::AcquireRelease>>("atomic-release"),
+    Entry::of::<SharedAtomic<atomic::AcqRel>>("atomic-acqrel"),
+    Entry::of::<SharedAtomic<atomic::SeqCst>>("atomic-seqcst"),
+    Entry::of::<SharedLoadStore>("load-store"),
+    Entry::only::<SharedHalves>("halves"),
+    Entry::only::<SharedUnsyncHalves>("unsync-halves"),
+    Entry::of::<SharedCas>("cas"),
+    Entry::of::<SharedMutex>("mutex"),
+    Entry::of::<SharedRwLock>("rwlock"),
+    Entry::of::<SharedSpin<locks::TestAndSet>>("spin-tas"),
+    Entry::of::<SharedSpin<locks::Ticket>>("spin-ticket"),
+    Entry::of::<SharedSharded>("sharded"),
+];
+
+pub fn lookup(name: &str) -> Option<&'static Entry> {
+    CONTESTANTS.iter().find(|e| e.name == name)
+}
+
+/// Restricts a command-line value to the registered names.
+pub fn names() -> PossibleValuesParser {
+    PossibleValuesParser::new(CONTESTANTS.iter().map(|e| e.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::Layout;
+    use clap::Parser;
+
+    /// Several threads hammering `Atomic128`s next to each other,
+    /// through the dynamically dispatched lineup, never lose an
+    /// update.
+    #[test]
+    fn u128_atomics_survive_a_dyn_lineup() {
+        let config = Config::parse_from([
+            "contest",
+            "--threads=4",
+            "--rounds=64",
+            "--reps=64",
+            "--seed=3",
+            "--width=u128",
+            "--contestants=atomic,cas,sharded",
+        ]);
+        let arena = Arena::new(Layout::SameLine, config.line_size);
+        let lineup: Vec<_> = config
+            .contestants
+            .iter()
+            .map(|name| (lookup(name).unwrap().build)(config.width, &arena))
+            .collect();
+
+        for (name, outcome) in config
+            .contestants
+            .iter()
+            .zip(harness::run_lineup(&config, lineup))
+        {
+            assert_eq!(outcome.error, 0, "{name} lost an update");
+        }
+    }
+}
@@ -0,0 +1,348 @@
+//! Everything a run produced, and the formats it can be written
+//! out in. The JSON and CSV layouts are meant to be stable, so
+//! add fields rather than renaming them.
+
+use crate::{
+    affinity,
+    cli::{Config, Mode},
+    diagnose::{Diagnosis, OperandLog},
+    harness::{Counter, RaceOutcome},
+    host::{Cache, Host},
+    layout::{Address, Layout},
+    stats::{Halves, TrialSummary},
+    timing::Timing,
+    word::Width,
+};
+use serde::Serialize;
+use std::{
+    io::{self, Write},
+    time::Duration,
+};
+
+/// Bumped whenever a field changes meaning or goes away. New
+/// fields, like `seed_hex`, don't bump it.
+const SCHEMA: u32 = 1;
+
+#[derive(Debug, Serialize)]
+pub struct Report<'a> {
+    pub schema: u32,
+    pub config: &'a Config,
+    /// `config.seed` as a string, for readers that lose precision
+    /// past 2^53.
+    pub seed_hex: String,
+    pub host: Host,
+    pub elapsed_ns: u64,
+    /// Whether the timings are leave-one-out differences of an
+    /// interleaved lineup, rather than measured directly.
+    pub attributed: bool,
+    pub contestants: Vec<ContestantReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ContestantReport {
+    pub name: &'static str,
+    pub layout: Layout,
+    pub summary: TrialSummary,
+    pub trials: Vec<TrialReport>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TrialReport {
+    pub trial: usize,
+    pub value: u128,
+    /// The same value as a string, for readers that lose precision
+    /// past 2^53.
+    pub value_hex: String,
+    /// The bits of `value` that are wrong, compared to the closest
+    /// value the workload allows.
+    pub error: u128,
+    pub error_hex: String,
+    pub corrupted: bool,
+    /// Which halves of the width the corruption is in.
+    pub corrupted_halves: Halves,
+    pub timing: Timing,
+    pub counters: Vec<Counter>,
+    pub address: Address,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diagnosis: Option<Diagnosis>,
+}
+
+impl ContestantReport {
+    /// `log` is the operand log and the most lost operands to look
+    /// for, if corrupted values should be diagnosed.
+    pub fn new(
+        name: &'static str,
+        layout: Layout,
+        width: Width,
+        outcomes: &[&RaceOutcome],
+        log: Option<&(OperandLog, usize)>,
+    ) -> Self {
+        let trials = outcomes
+            .iter()
+            .enumerate()
+            .map(|(trial, outcome)| {
+                let corrupted = outcome.error != 0;
+                TrialReport {
+                    trial,
+                    value: outcome.value,
+                    value_hex: width.hex(outcome.value),
+                    error: outcome.error,
+                    error_hex: width.hex(outcome.error),
+                    corrupted,
+                    corrupted_halves: Halves::of(outcome.error, width),
+                    timing: outcome.timing.clone(),
+                    counters: outcome.counters.clone(),
+                    address: outcome.address,
+                    diagnosis: log
+                        .filter(|_| corrupted)
+                        .map(|(log, max_lost)| log.explain(outcome.error, *max_lost)),
+                }
+            })
+            .collect();
+
+        Self {
+            name,
+            layout,
+            summary: TrialSummary::new(outcomes.iter().copied(), width),
+            trials,
+        }
+    }
+}
+
+impl<'a> Report<'a> {
+    pub fn new(config: &'a Config, elapsed: Duration, contestants: Vec<ContestantReport>) -> Self {
+        Self {
+            schema: SCHEMA,
+            config,
+            seed_hex: Width::U64.hex(config.seed.expect("resolved before racing").into()),
+            host: Host::get().clone(),
+            elapsed_ns: elapsed.as_nanos() as u64,
+            attributed: config.mode == Mode::Interleaved && config.contestants.len() > 1,
+            contestants,
+        }
+    }
+
+    pub fn write_text(&self, mut w: impl Write) -> io::Result<()> {
+        let config = self.config;
+        writeln!(
+            w,
+            "threads: {}, rounds: {}, reps: {}, contestants: {}, workload: {}, width: {}, layouts: {}, line size: {}, mode: {}, pin: {}, gate: {}, trials: {}, seed: {}",
+            config.threads,
+            config.rounds,
+            config.reps,
+            config.contestants.join(","),
+            config.workload,
+            config.width,
+            config.layouts.iter().map(Layout::to_string).collect::<Vec<_>>().join(","),
+            config.line_size,
+            config.mode,
+            config.pin,
+            config.gate,
+            config.trials,
+            config.seed.expect("resolved before racing"),
+        )?;
+        writeln!(
+            w,
+            "host: {} ({}/{}, {} cpus)",
+            self.host.hostname, self.host.os, self.host.arch, self.host.cpus
+        )?;
+        writeln!(
+            w,
+            "cpu: {}, packages: {}, cores: {}, threads per core: {}, numa nodes: {}",
+            self.host.model.as_deref().unwrap_or("unknown"),
+            self.host.packages,
+            self.host.cores,
+            self.host.threads_per_core,
+            self.host.numa_nodes,
+        )?;
+        if !self.host.caches.is_empty() {
+            let caches: Vec<_> = self.host.caches.iter().map(Cache::to_string).collect();
+            writeln!(w, "caches: {}", caches.join(", "))?;
+        }
+        if let Some(cpus) = &config.cpus {
+            writeln!(w, "threads pinned to cpus {}", affinity::join(cpus))?;
+        }
+
+        // Contestants come grouped by layout, in the order given.
+        let by_layout = config.layouts.len() > 1;
+        for group in self.contestants.chunk_by(|a, b| a.layout == b.layout) {
+            if by_layout {
+                writeln!(w, "layout {}:", group[0].layout)?;
+            }
+            for contestant in group {
+                self.write_contestant(&mut w, contestant)?;
+            }
+            // Isolated contestants never race at the same time, so
+            // sharing a line with each other doesn't matter.
+            if config.mode != Mode::Isolated {
+                for (line, names) in shared_lines(group) {
+                    writeln!(w, "{} share cache line {line:#x}", names.join(" and "))?;
+                }
+            }
+        }
+
+        for contestant in &self.contestants {
+            for trial in &contestant.trials {
+                let Some(diagnosis) = &trial.diagnosis else {
+                    continue;
+                };
+                write!(w, "{} in trial {}", contestant.name, trial.trial)?;
+                if by_layout {
+                    write!(w, " with layout {}", contestant.layout)?;
+                }
+                writeln!(w, ": {}", trial.value_hex)?;
+                for operand in &diagnosis.lost {
+                    writeln!(w, "  lost {operand}")?;
+                }
+                if !diagnosis.explained {
+                    let max_lost = config.diagnose.unwrap_or_default();
+                    writeln!(w, "  not the xor of {max_lost} or fewer operands")?;
+                }
+            }
+        }
+
+        if self.attributed {
+            writeln!(w, "interleaved timings are leave-one-out differences")?;
+        }
+        writeln!(w, "took {:.0?}", Duration::from_nanos(self.elapsed_ns))
+    }
+
+    /// One contestant's results, in full if there was one trial
+    /// and summarized otherwise.
+    fn write_contestant(
+        &self,
+        w: &mut impl Write,
+        contestant: &ContestantReport,
+    ) -> io::Result<()> {
+        let width = self.config.width;
+        if let [trial] = contestant.trials.as_slice() {
+            write!(w, "{}: {}", contestant.name, width.binary(trial.value))?;
+            match trial.corrupted_halves {
+                Halves::High | Halves::Low => {
+                    writeln!(w, " (only the {} half)", trial.corrupted_halves)?
+                }
+                Halves::None | Halves::Both => writeln!(w)?,
+            }
+            if trial.corrupted && trial.error != trial.value {
+                let pad = contestant.name.len();
+                writeln!(w, "{:pad$}  {} wrong", "", width.binary(trial.error))?;
+            }
+            writeln!(w, "  {}", trial.timing)?;
+            for counter in &trial.counters {
+                writeln!(w, "  {counter}")?;
+            }
+            writeln!(w, "  at {}", trial.address)
+        } else {
+            writeln!(w, "{}:", contestant.name)?;
+            writeln!(w, "{}", contestant.summary)?;
+            let trials = &contestant.trials;
+            for (i, counter) in trials[0].counters.iter().enumerate() {
+                let total: u64 = trials.iter().map(|t| t.counters[i].total()).sum();
+                writeln!(
+                    w,
+                    "  {} {total} over all trials, mean {:.1} per trial",
+                    counter.name,
+                    total as f64 / trials.len() as f64
+                )?;
+            }
+            // Every trial allocates afresh, so only the first one's
+            // address is shown.
+            writeln!(w, "  first trial at {}", trials[0].address)
+        }
+    }
+
+    pub fn write_json(&self, mut w: impl Write) -> io::Result<()> {
+        serde_json::to_writer_pretty(&mut w, self)?;
+        writeln!(w)
+    }
+
+    /// One row per contestant per trial. Summaries are left out,
+    /// since they're easy to recompute from the rows.
+    pub fn write_csv(&self, mut w: impl Write) -> io::Result<()> {
+        writeln!(
+            w,
+            "schema,hostname,os,arch,cpus,threads,rounds,reps,mode,seed,\
+             contestant,trial,value,corrupted,wall_ns,mean_thread_ns,ns_per_op,counters,corrupted_halves,error,workload,width,layout,address,line,page_offset,pin,\
+             packages,cores,threads_per_core,numa_nodes,line_size,model,\
+             gate,max_start_skew_ns"
+        )?;
+
+        let config = self.config;
+        for contestant in &self.contestants {
+            for trial in &contestant.trials {
+                writeln!(
+                    w,
+                    "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+                    self.schema,
+                    csv_field(&self.host.hostname),
+                    self.host.os,
+                    self.host.arch,
+                    self.host.cpus,
+                    config.threads,
+                    config.rounds,
+                    config.reps,
+                    config.mode,
+                    config.seed.expect("resolved before racing"),
+                    contestant.name,
+                    trial.trial,
+                    trial.value,
+                    trial.corrupted,
+                    trial.timing.wall.as_nanos(),
+                    trial.timing.mean_thread().as_nanos(),
+                    trial.timing.ns_per_op(),
+                    csv_counters(&trial.counters),
+                    trial.corrupted_halves,
+                    trial.error,
+                    config.workload,
+                    config.width,
+                    contestant.layout,
+                    trial.address.address,
+                    trial.address.line,
+                    trial.address.page_offset,
+                    csv_field(&config.pin.to_string()),
+                    self.host.packages,
+                    self.host.cores,
+                    self.host.threads_per_core,
+                    self.host.numa_nodes,
+                    config.line_size,
+                    csv_field(self.host.model.as_deref().unwrap_or_default()),
+                    config.gate,
+                    trial.timing.max_start_skew().as_nanos(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Cache lines that more than one contestant's shared value
+/// started on in the first trial, with those contestants' names.
+fn shared_lines(group: &[ContestantReport]) -> Vec<(usize, Vec<&'static str>)> {
+    let mut lines: Vec<(usize, Vec<&'static str>)> = Vec::new();
+    for contestant in group {
+        let line = contestant.trials[0].address.line;
+        match lines.iter_mut().find(|(l, _)| *l == line) {
+            Some((_, names)) => names.push(contestant.name),
+            None => lines.push((line, vec![contestant.name])),
+        }
+    }
+    lines.retain(|(_, names)| names.len() > 1);
+    lines
+}
+
+/// Counter totals as `name=total`, separated by semicolons.
+fn csv_counters(counters: &[Counter]) -> String {
+    let fields: Vec<_> = counters
+        .iter()
+        .map(|c| format!("{}={}", c.name, c.total()))
+        .collect();
+    fields.join(";")
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
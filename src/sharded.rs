@@ -0,0 +1,128 @@
+//! The `sharded` module is how you'd actually do this: every
+//! thread updates a slot of its own, on its own cache line, and
+//! reading the value combines all the slots. Nothing is
+//! contended until the very end.
+
+use crate::{
+    layout::{Arena, Shared},
+    op::{self, OpKind, Operation},
+    race::{AnyWidth, Race},
+    word::{AtomicWord, Word},
+};
+use std::sync::{
+    atomic::{AtomicU8, Ordering},
+    Arc, Mutex,
+};
+
+/// Aligned to two cache lines, since some prefetchers pull in
+/// lines in pairs.
+#[repr(align(128))]
+#[derive(Default)]
+pub struct CachePadded<T>(pub T);
+
+/// How the slots are folded back into one value. It depends on
+/// which operations they saw, so each slot remembers.
+#[derive(Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+enum Combine {
+    Unused,
+    Xor,
+    /// Adds and subtracts.
+    Sum,
+    /// Ors and ands. A slot only ever clears bits it set itself,
+    /// so or-ing the slots together is exact.
+    Bits,
+    /// Swaps have no order across slots, but any slot holds the
+    /// last value its thread swapped in.
+    Last,
+}
+
+impl Combine {
+    const fn of(kind: OpKind) -> Self {
+        match kind {
+            OpKind::Xor => Self::Xor,
+            OpKind::Add | OpKind::Sub => Self::Sum,
+            OpKind::Or | OpKind::And => Self::Bits,
+            OpKind::Swap => Self::Last,
+        }
+    }
+
+    fn from_u8(n: u8) -> Self {
+        [Self::Unused, Self::Xor, Self::Sum, Self::Bits, Self::Last][n as usize]
+    }
+}
+
+#[derive(Default)]
+struct Slot<W: Word> {
+    value: W::Atomic,
+    combine: AtomicU8,
+}
+
+type SlotRef<W> = Arc<CachePadded<Slot<W>>>;
+
+pub struct SharedSharded<W: Word = u64> {
+    slots: Shared<Mutex<Vec<SlotRef<W>>>>,
+    mine: SlotRef<W>,
+}
+
+impl<W: Word> SharedSharded<W> {
+    fn with_slot(slots: Shared<Mutex<Vec<SlotRef<W>>>>) -> Self {
+        let mine = SlotRef::default();
+        slots.lock().unwrap().push(mine.clone());
+        Self { slots, mine }
+    }
+}
+
+// Every clone gets a fresh slot, which is what makes the slots
+// per-thread.
+impl<W: Word> Clone for SharedSharded<W> {
+    fn clone(&self) -> Self {
+        Self::with_slot(self.slots.clone())
+    }
+}
+
+impl<W: Word> Race for SharedSharded<W> {
+    type Word = W;
+
+    fn new(arena: &Arena) -> Self {
+        Self::with_slot(arena.place(Mutex::default()))
+    }
+
+    fn get(&self) -> W {
+        let slots = self.slots.lock().unwrap();
+        let used = slots.iter().filter_map(|slot| {
+            let combine = Combine::from_u8(slot.0.combine.load(Ordering::Relaxed));
+            (combine != Combine::Unused).then(|| (combine, slot.0.value.load(Ordering::Relaxed)))
+        });
+
+        used.fold(W::default(), |acc, (combine, value)| match combine {
+            Combine::Unused => acc,
+            Combine::Xor => acc ^ value,
+            Combine::Sum => acc.wrapping_add(value),
+            Combine::Bits => acc | value,
+            Combine::Last => value,
+        })
+    }
+
+    // The slots are per-thread; the list of them is all the
+    // threads have in common.
+    fn address(&self) -> usize {
+        self.slots.as_ptr() as usize
+    }
+
+    fn apply<O: Operation>(&self, operand: W) {
+        let slot = &self.mine.0;
+        let combine = Combine::of(O::KIND) as u8;
+        if slot.combine.load(Ordering::Relaxed) != combine {
+            slot.combine.store(combine, Ordering::Relaxed);
+        }
+        // Nobody else writes to this slot, so the RMW never has to
+        // fight for the cache line. It's still atomic in case a
+        // clone does get shared between threads.
+        op::atomic_rmw::<O, W>(&slot.value, operand, Ordering::Relaxed);
+    }
+}
+
+impl<W: Word> AnyWidth for SharedSharded<W> {
+    type At<V: Word> = SharedSharded<V>;
+}
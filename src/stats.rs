@@ -0,0 +1,326 @@
+//! Summaries over repeated trials of the same race.
+
+use crate::{harness::RaceOutcome, word::Width};
+use serde::Serialize;
+use std::{fmt, time::Duration};
+
+/// Two-sided 95% normal quantile.
+const Z_95: f64 = 1.959_964;
+
+/// Descriptive statistics over a set of samples.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Summary {
+    pub n: usize,
+    pub mean: f64,
+    /// 95% confidence interval for the mean, using the normal
+    /// approximation.
+    pub mean_ci95: (f64, f64),
+    pub median: f64,
+    pub stddev: f64,
+    pub min: f64,
+    pub max: f64,
+}
+
+impl Summary {
+    pub fn new(samples: &[f64]) -> Self {
+        let n = samples.len();
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+
+        let mean = sorted.iter().sum::<f64>() / n.max(1) as f64;
+        let median = match n {
+            0 => 0.0,
+            _ if n % 2 == 1 => sorted[n / 2],
+            _ => (sorted[n / 2 - 1] + sorted[n / 2]) / 2.0,
+        };
+        // Sample standard deviation; a single trial has none.
+        let stddev = if n > 1 {
+            let sq: f64 = sorted.iter().map(|x| (x - mean).powi(2)).sum();
+            (sq / (n - 1) as f64).sqrt()
+        } else {
+            0.0
+        };
+
+        let half = Z_95 * stddev / (n.max(1) as f64).sqrt();
+
+        Self {
+            n,
+            mean,
+            mean_ci95: (mean - half, mean + half),
+            median,
+            stddev,
+            min: sorted.first().copied().unwrap_or_default(),
+            max: sorted.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+/// 95% Wilson score interval for a proportion. Unlike the normal
+/// approximation it behaves when nothing (or everything) was
+/// corrupted.
+pub fn wilson_ci95(hits: usize, n: usize) -> (f64, f64) {
+    if n == 0 {
+        return (0.0, 1.0);
+    }
+    let (n, p) = (n as f64, hits as f64 / n as f64);
+    let z2 = Z_95 * Z_95;
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let half = Z_95 * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / (1.0 + z2 / n);
+    ((center - half).max(0.0), (center + half).min(1.0))
+}
+
+/// Which halves of a value are nonzero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Halves {
+    None,
+    High,
+    Low,
+    Both,
+}
+
+impl Halves {
+    pub fn of(value: u128, width: Width) -> Self {
+        let half = width.bits() / 2;
+        match (value >> half, value & (width.mask() >> half)) {
+            (0, 0) => Self::None,
+            (_, 0) => Self::High,
+            (0, _) => Self::Low,
+            _ => Self::Both,
+        }
+    }
+}
+
+impl fmt::Display for Halves {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::None => "none",
+            Self::High => "high",
+            Self::Low => "low",
+            Self::Both => "both",
+        })
+    }
+}
+
+/// Which bits of the final value were wrong, across trials.
+#[derive(Debug, Clone, Serialize)]
+pub struct BitHistogram {
+    pub trials: usize,
+    /// How many trials ended with each bit wrong, indexed by bit.
+    /// One entry per bit of the race's width.
+    pub bits: Vec<usize>,
+    /// How many trials ended with each number of wrong bits.
+    pub popcounts: Vec<usize>,
+    /// Corrupted trials where only the high or only the low half
+    /// of the bits were wrong. A lot of these hints at split
+    /// stores.
+    pub high_only: usize,
+    pub low_only: usize,
+    #[serde(skip)]
+    width: Width,
+}
+
+impl BitHistogram {
+    /// Takes each trial's [`RaceOutcome::error`].
+    pub fn new(errors: impl IntoIterator<Item = u128>, width: Width) -> Self {
+        let bits = width.bits() as usize;
+        let mut hist = Self {
+            trials: 0,
+            bits: vec![0; bits],
+            popcounts: vec![0; bits + 1],
+            high_only: 0,
+            low_only: 0,
+            width,
+        };
+        for error in errors {
+            hist.trials += 1;
+            hist.popcounts[error.count_ones() as usize] += 1;
+            for (bit, count) in hist.bits.iter_mut().enumerate() {
+                *count += (error >> bit) as usize & 1;
+            }
+            match Halves::of(error, width) {
+                Halves::High => hist.high_only += 1,
+                Halves::Low => hist.low_only += 1,
+                Halves::None | Halves::Both => {}
+            }
+        }
+        hist
+    }
+
+    /// One character per bit, most significant first so it lines
+    /// up with the value printed in binary. `.` means never wrong,
+    /// otherwise `1`-`9` is how often it was, rounded up to the
+    /// nearest ninth.
+    pub fn columns(&self) -> String {
+        self.bits
+            .iter()
+            .rev()
+            .map(|&count| match count {
+                0 => '.',
+                _ => {
+                    let ninths = (9 * count).div_ceil(self.trials.max(1));
+                    char::from_digit(ninths as u32, 10).unwrap()
+                }
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for BitHistogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (low, high) = self.bits.split_at(self.width.bits() as usize / 2);
+        writeln!(f, "  bits wrong: {}", self.columns())?;
+        writeln!(
+            f,
+            "  bits wrong in high half {}, low half {}; trials with only high {}, only low {}",
+            high.iter().sum::<usize>(),
+            low.iter().sum::<usize>(),
+            self.high_only,
+            self.low_only
+        )?;
+        let popcounts: Vec<_> = self
+            .popcounts
+            .iter()
+            .enumerate()
+            .filter(|(_, &n)| n > 0)
+            .map(|(ones, n)| format!("{ones}x{n}"))
+            .collect();
+        write!(f, "  popcounts: {}", popcounts.join(" "))
+    }
+}
+
+/// One contestant's results across every trial.
+#[derive(Debug, Clone, Serialize)]
+pub struct TrialSummary {
+    pub trials: usize,
+    pub corrupted: usize,
+    pub corrupted_ci95: (f64, f64),
+    /// Wall time, in nanoseconds.
+    #[serde(rename = "wall_ns")]
+    pub wall: Summary,
+    pub ns_per_op: Summary,
+    pub bits: BitHistogram,
+}
+
+impl TrialSummary {
+    pub fn new<'a>(outcomes: impl IntoIterator<Item = &'a RaceOutcome>, width: Width) -> Self {
+        let outcomes: Vec<_> = outcomes.into_iter().collect();
+        let wall: Vec<_> = outcomes
+            .iter()
+            .map(|o| o.timing.wall.as_nanos() as f64)
+            .collect();
+        let ns_per_op: Vec<_> = outcomes.iter().map(|o| o.timing.ns_per_op()).collect();
+
+        let corrupted = outcomes.iter().filter(|o| o.error != 0).count();
+
+        Self {
+            trials: outcomes.len(),
+            corrupted,
+            corrupted_ci95: wilson_ci95(corrupted, outcomes.len()),
+            wall: Summary::new(&wall),
+            ns_per_op: Summary::new(&ns_per_op),
+            bits: BitHistogram::new(outcomes.iter().map(|o| o.error), width),
+        }
+    }
+
+    pub fn corrupted_fraction(&self) -> f64 {
+        self.corrupted as f64 / self.trials.max(1) as f64
+    }
+}
+
+impl fmt::Display for TrialSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ns = |x: f64| Duration::from_nanos(x.max(0.0) as u64);
+        let (lo, hi) = self.corrupted_ci95;
+        writeln!(
+            f,
+            "  corrupted {}/{} ({:.1}%, 95% CI {:.1}%..{:.1}%)",
+            self.corrupted,
+            self.trials,
+            100.0 * self.corrupted_fraction(),
+            100.0 * lo,
+            100.0 * hi
+        )?;
+
+        let w = &self.wall;
+        let (lo, hi) = w.mean_ci95;
+        writeln!(
+            f,
+            "  wall mean {:.0?} (95% CI {:.0?}..{:.0?}), median {:.0?}, stddev {:.0?}, min {:.0?}, max {:.0?}",
+            ns(w.mean),
+            ns(lo),
+            ns(hi),
+            ns(w.median),
+            ns(w.stddev),
+            ns(w.min),
+            ns(w.max)
+        )?;
+
+        let op = &self.ns_per_op;
+        let (lo, hi) = op.mean_ci95;
+        write!(
+            f,
+            "  ns/op mean {:.2} (95% CI {:.2}..{:.2}), median {:.2}, stddev {:.2}, min {:.2}, max {:.2}",
+            op.mean, lo, hi, op.median, op.stddev, op.min, op.max
+        )?;
+
+        if self.corrupted > 0 {
+            write!(f, "\n{}", self.bits)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-9
+    }
+
+    #[test]
+    fn medians_of_odd_and_even_counts() {
+        assert_eq!(Summary::new(&[3.0, 1.0, 2.0]).median, 2.0);
+        assert_eq!(Summary::new(&[4.0, 1.0, 3.0, 2.0]).median, 2.5);
+        assert_eq!(Summary::new(&[]).median, 0.0);
+    }
+
+    #[test]
+    fn stddev_is_the_sample_one() {
+        let summary = Summary::new(&[2.0, 4.0, 4.0, 4.0, 5.0, 5.0, 7.0, 9.0]);
+        assert_eq!(summary.mean, 5.0);
+        assert!(close(summary.stddev, (32.0f64 / 7.0).sqrt()));
+        assert_eq!(Summary::new(&[7.0]).stddev, 0.0);
+    }
+
+    #[test]
+    fn wilson_interval_at_the_extremes() {
+        let z2 = Z_95 * Z_95;
+
+        let (lo, hi) = wilson_ci95(0, 10);
+        assert!(close(lo, 0.0));
+        assert!(close(hi, z2 / (10.0 + z2)));
+
+        let (lo, hi) = wilson_ci95(10, 10);
+        assert!(close(lo, 10.0 / (10.0 + z2)));
+        assert!(close(hi, 1.0));
+
+        assert_eq!(wilson_ci95(0, 0), (0.0, 1.0));
+    }
+
+    #[test]
+    fn columns_round_up_to_ninths() {
+        // Out of ten trials, bit 0 is wrong in all of them, bit 1 in
+        // five, bit 2 in one and bit 3 in nine.
+        let errors = (0..10).map(|trial| {
+            let mut error = 0b1;
+            error |= u128::from(trial < 5) << 1;
+            error |= u128::from(trial == 0) << 2;
+            error |= u128::from(trial < 9) << 3;
+            error
+        });
+        let hist = BitHistogram::new(errors, Width::U8);
+        assert_eq!(hist.columns(), "....9159");
+    }
+}
@@ -0,0 +1,90 @@
+//! How long each contestant took, broken down per thread.
+
+use serde::{ser::SerializeStruct, Serialize, Serializer};
+use std::{fmt, time::Duration};
+
+#[derive(Debug, Clone)]
+pub struct Timing {
+    /// Wall time attributed to the contestant.
+    pub wall: Duration,
+    /// Time each thread spent on the contestant.
+    pub threads: Vec<Duration>,
+    /// How long after the first thread each thread started.
+    pub start_skew: Vec<Duration>,
+    /// Number of operations the workload had each thread apply to
+    /// the contestant.
+    pub ops_per_thread: u64,
+}
+
+impl Timing {
+    pub fn mean_thread(&self) -> Duration {
+        let total: Duration = self.threads.iter().sum();
+        total / self.threads.len().max(1) as u32
+    }
+
+    /// How far behind the first thread the last one started.
+    pub fn max_start_skew(&self) -> Duration {
+        self.start_skew.iter().max().copied().unwrap_or_default()
+    }
+
+    /// Average latency of a single operation, as seen by one
+    /// thread.
+    pub fn ns_per_op(&self) -> f64 {
+        self.mean_thread().as_nanos() as f64 / self.ops_per_thread.max(1) as f64
+    }
+
+    /// The time left over once `baseline` is taken away, thread by
+    /// thread. Used to pull one contestant's share out of an
+    /// interleaved run.
+    pub fn saturating_sub(&self, baseline: &Timing) -> Timing {
+        Timing {
+            wall: self.wall.saturating_sub(baseline.wall),
+            threads: self
+                .threads
+                .iter()
+                .zip(&baseline.threads)
+                .map(|(t, b)| t.saturating_sub(*b))
+                .collect(),
+            start_skew: self.start_skew.clone(),
+            ops_per_thread: self.ops_per_thread,
+        }
+    }
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let min = self.threads.iter().min().copied().unwrap_or_default();
+        let max = self.threads.iter().max().copied().unwrap_or_default();
+        write!(
+            f,
+            "wall {:.0?}, per thread {:.0?}..{:.0?} (mean {:.0?}), {:.2} ns/op, start skew up to {:.0?}",
+            self.wall,
+            min,
+            max,
+            self.mean_thread(),
+            self.ns_per_op(),
+            self.max_start_skew()
+        )
+    }
+}
+
+/// Durations go out as plain nanoseconds, which is what anything
+/// reading the report wants to plot.
+impl Serialize for Timing {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let nanos = |d: &Duration| d.as_nanos() as u64;
+        let mut s = serializer.serialize_struct("Timing", 5)?;
+        s.serialize_field("wall_ns", &nanos(&self.wall))?;
+        s.serialize_field(
+            "thread_ns",
+            &self.threads.iter().map(nanos).collect::<Vec<_>>(),
+        )?;
+        s.serialize_field(
+            "start_skew_ns",
+            &self.start_skew.iter().map(nanos).collect::<Vec<_>>(),
+        )?;
+        s.serialize_field("ops_per_thread", &self.ops_per_thread)?;
+        s.serialize_field("ns_per_op", &self.ns_per_op())?;
+        s.end()
+    }
+}
@@ -0,0 +1,132 @@
+//! Which CPUs the machine has and how they're related, as Linux
+//! describes them under `/sys/devices/system`.
+
+use serde::Serialize;
+use std::{fs, path::Path, thread};
+
+const CPU_DIR: &str = "/sys/devices/system/cpu";
+const NODE_DIR: &str = "/sys/devices/system/node";
+
+/// One hardware thread.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Cpu {
+    pub id: usize,
+    pub package: usize,
+    /// Only unique within `package`.
+    pub core: usize,
+    pub node: usize,
+}
+
+impl Cpu {
+    /// Whether both are hardware threads of the same core.
+    pub fn is_sibling(&self, other: &Cpu) -> bool {
+        (self.package, self.core) == (other.package, other.core)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Topology {
+    /// Every online CPU, by id.
+    pub cpus: Vec<Cpu>,
+}
+
+impl Topology {
+    /// Reads sysfs. Anything missing is filled in as if every CPU
+    /// were its own core on one package and one NUMA node.
+    pub fn detect() -> Self {
+        let ids = read(CPU_DIR, "online")
+            .and_then(|s| parse_list(&s))
+            .unwrap_or_else(|| {
+                let n = thread::available_parallelism().map_or(1, |n| n.get());
+                (0..n).collect()
+            });
+
+        let nodes: Vec<(usize, Vec<usize>)> = read(NODE_DIR, "online")
+            .and_then(|s| parse_list(&s))
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|node| {
+                let cpus = read(NODE_DIR, &format!("node{node}/cpulist"))?;
+                Some((node, parse_list(&cpus)?))
+            })
+            .collect();
+
+        let cpus = ids
+            .into_iter()
+            .map(|id| {
+                let topology = |name: &str| {
+                    read(CPU_DIR, &format!("cpu{id}/topology/{name}")).and_then(|s| s.parse().ok())
+                };
+                Cpu {
+                    id,
+                    package: topology("physical_package_id").unwrap_or(0),
+                    core: topology("core_id").unwrap_or(id),
+                    node: nodes
+                        .iter()
+                        .find(|(_, cpus)| cpus.contains(&id))
+                        .map_or(0, |&(node, _)| node),
+                }
+            })
+            .collect();
+
+        Self { cpus }
+    }
+
+    pub fn nodes(&self) -> Vec<usize> {
+        let mut nodes: Vec<_> = self.cpus.iter().map(|c| c.node).collect();
+        nodes.sort_unstable();
+        nodes.dedup();
+        nodes
+    }
+
+    /// How many hardware threads of `cpu`'s core come before it.
+    pub fn sibling_rank(&self, cpu: &Cpu) -> usize {
+        self.cpus
+            .iter()
+            .filter(|c| c.is_sibling(cpu) && c.id < cpu.id)
+            .count()
+    }
+}
+
+/// Parses a kernel CPU list like `0-3,8,10-11`.
+pub fn parse_list(s: &str) -> Option<Vec<usize>> {
+    let mut ids = Vec::new();
+    for range in s.trim().split(',').filter(|r| !r.is_empty()) {
+        match range.split_once('-') {
+            Some((first, last)) => ids.extend(first.parse::<usize>().ok()?..=last.parse().ok()?),
+            None => ids.push(range.parse().ok()?),
+        }
+    }
+    Some(ids)
+}
+
+fn read(dir: &str, file: &str) -> Option<String> {
+    fs::read_to_string(Path::new(dir).join(file))
+        .ok()
+        .map(|s| s.trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_cpu_lists() {
+        assert_eq!(
+            parse_list("0-3,8,10-11\n"),
+            Some(vec![0, 1, 2, 3, 8, 10, 11])
+        );
+        assert_eq!(parse_list("5"), Some(vec![5]));
+        assert_eq!(parse_list("2-2"), Some(vec![2]));
+        assert_eq!(parse_list(""), Some(vec![]));
+        assert_eq!(parse_list("0,,1"), Some(vec![0, 1]));
+    }
+
+    #[test]
+    fn rejects_malformed_cpu_lists() {
+        assert_eq!(parse_list("a"), None);
+        assert_eq!(parse_list("1-"), None);
+        assert_eq!(parse_list("-1"), None);
+        assert_eq!(parse_list("0-3,x"), None);
+    }
+}
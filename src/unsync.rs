@@ -0,0 +1,85 @@
+//! This module contains a type that erroneously implements
+//! Send and Sync without actually synchronising data access.
+//! Let's see what happens.
+
+use crate::{
+    layout::{Arena, Shared},
+    op::Operation,
+    race::{AnyWidth, Race},
+    word::Word,
+};
+use std::cell::UnsafeCell;
+
+#[derive(Clone)]
+pub struct SharedUnsync<W = u64>(Shared<UnsafeCell<W>>);
+
+impl<W: Word> Race for SharedUnsync<W> {
+    type Word = W;
+
+    fn new(arena: &Arena) -> Self {
+        Self(arena.place(UnsafeCell::new(W::default())))
+    }
+
+    fn get(&self) -> W {
+        unsafe { *self.0.get() }
+    }
+
+    fn address(&self) -> usize {
+        self.0.as_ptr() as usize
+    }
+
+    fn apply<O: Operation>(&self, operand: W) {
+        // SAFETY: very unsafe.
+        unsafe {
+            let value = self.0.get();
+            *value = O::apply(*value, operand);
+        }
+    }
+}
+
+// SAFETY: still unsafe.
+unsafe impl<W: Send> Send for SharedUnsync<W> {}
+unsafe impl<W: Send> Sync for SharedUnsync<W> {}
+
+impl<W: Word> AnyWidth for SharedUnsync<W> {
+    type At<V: Word> = SharedUnsync<V>;
+}
+
+/// Like [`SharedUnsync`], but every load and store is volatile.
+/// The compiler has to emit each one, so it can't hoist the xors
+/// out of the loop or cancel them against each other. Whatever
+/// happens to the value is down to the hardware.
+#[derive(Clone)]
+pub struct SharedVolatile<W = u64>(Shared<UnsafeCell<W>>);
+
+impl<W: Word> Race for SharedVolatile<W> {
+    type Word = W;
+
+    fn new(arena: &Arena) -> Self {
+        Self(arena.place(UnsafeCell::new(W::default())))
+    }
+
+    fn get(&self) -> W {
+        unsafe { self.0.get().read_volatile() }
+    }
+
+    fn address(&self) -> usize {
+        self.0.as_ptr() as usize
+    }
+
+    fn apply<O: Operation>(&self, operand: W) {
+        // SAFETY: still very unsafe, just not optimized away.
+        unsafe {
+            let value = self.0.get().read_volatile();
+            self.0.get().write_volatile(O::apply(value, operand));
+        }
+    }
+}
+
+// SAFETY: still unsafe.
+unsafe impl<W: Send> Send for SharedVolatile<W> {}
+unsafe impl<W: Send> Sync for SharedVolatile<W> {}
+
+impl<W: Word> AnyWidth for SharedVolatile<W> {
+    type At<V: Word> = SharedVolatile<V>;
+}
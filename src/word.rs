@@ -0,0 +1,333 @@
+//! The integer widths a race can run at. Contestants are generic
+//! over their [`Word`], so every width gets its own hot loop, and
+//! the harness carries values around as `u128` in between.
+
+use clap::ValueEnum;
+use serde::Serialize;
+use std::{
+    cell::UnsafeCell,
+    fmt, hint,
+    ops::{BitAnd, BitOr, BitXor, Not},
+    sync::atomic::{AtomicBool, AtomicU16, AtomicU32, AtomicU64, AtomicU8, Ordering},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Width {
+    U8,
+    U16,
+    U32,
+    U64,
+    /// There's no stable `AtomicU128`, so atomics use
+    /// `cmpxchg16b` where the CPU has it and a lock otherwise.
+    U128,
+}
+
+impl Width {
+    pub const fn bits(self) -> u32 {
+        match self {
+            Self::U8 => 8,
+            Self::U16 => 16,
+            Self::U32 => 32,
+            Self::U64 => 64,
+            Self::U128 => 128,
+        }
+    }
+
+    /// Every bit a value of this width can have set.
+    pub const fn mask(self) -> u128 {
+        u128::MAX >> (128 - self.bits())
+    }
+
+    /// `value` in hex, padded to the full width.
+    pub fn hex(self, value: u128) -> String {
+        format!(
+            "{:#0digits$x}",
+            value,
+            digits = self.bits() as usize / 4 + 2
+        )
+    }
+
+    /// `value` in binary, padded to the full width.
+    pub fn binary(self, value: u128) -> String {
+        format!("{:0digits$b}", value, digits = self.bits() as usize)
+    }
+}
+
+impl fmt::Display for Width {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// An unsigned integer a contestant can race on.
+pub trait Word:
+    Copy
+    + Default
+    + Eq
+    + Send
+    + Sync
+    + Into<u128>
+    + BitXor<Output = Self>
+    + BitOr<Output = Self>
+    + BitAnd<Output = Self>
+    + Not<Output = Self>
+    + 'static
+{
+    const WIDTH: Width;
+
+    /// The atomic version of this integer.
+    type Atomic: AtomicWord<Self>;
+
+    /// The low bits of `value`.
+    fn truncate(value: u128) -> Self;
+
+    fn wrapping_add(self, other: Self) -> Self;
+    fn wrapping_sub(self, other: Self) -> Self;
+}
+
+/// The parts of the standard atomic integers' API the contestants
+/// use, so they can be generic over width.
+pub trait AtomicWord<W>: Default + Send + Sync + 'static {
+    fn load(&self, order: Ordering) -> W;
+    fn store(&self, value: W, order: Ordering);
+    fn swap(&self, value: W, order: Ordering) -> W;
+    fn fetch_xor(&self, value: W, order: Ordering) -> W;
+    fn fetch_add(&self, value: W, order: Ordering) -> W;
+    fn fetch_sub(&self, value: W, order: Ordering) -> W;
+    fn fetch_or(&self, value: W, order: Ordering) -> W;
+    fn fetch_and(&self, value: W, order: Ordering) -> W;
+    fn compare_exchange_weak(
+        &self,
+        current: W,
+        new: W,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<W, W>;
+}
+
+macro_rules! impl_word {
+    ($($word:ty, $atomic:ty, $width:ident;)+) => {$(
+        impl Word for $word {
+            const WIDTH: Width = Width::$width;
+            type Atomic = $atomic;
+
+            fn truncate(value: u128) -> Self {
+                value as Self
+            }
+
+            fn wrapping_add(self, other: Self) -> Self {
+                <$word>::wrapping_add(self, other)
+            }
+
+            fn wrapping_sub(self, other: Self) -> Self {
+                <$word>::wrapping_sub(self, other)
+            }
+        }
+
+        impl AtomicWord<$word> for $atomic {
+            fn load(&self, order: Ordering) -> $word {
+                <$atomic>::load(self, order)
+            }
+
+            fn store(&self, value: $word, order: Ordering) {
+                <$atomic>::store(self, value, order)
+            }
+
+            fn swap(&self, value: $word, order: Ordering) -> $word {
+                <$atomic>::swap(self, value, order)
+            }
+
+            fn fetch_xor(&self, value: $word, order: Ordering) -> $word {
+                <$atomic>::fetch_xor(self, value, order)
+            }
+
+            fn fetch_add(&self, value: $word, order: Ordering) -> $word {
+                <$atomic>::fetch_add(self, value, order)
+            }
+
+            fn fetch_sub(&self, value: $word, order: Ordering) -> $word {
+                <$atomic>::fetch_sub(self, value, order)
+            }
+
+            fn fetch_or(&self, value: $word, order: Ordering) -> $word {
+                <$atomic>::fetch_or(self, value, order)
+            }
+
+            fn fetch_and(&self, value: $word, order: Ordering) -> $word {
+                <$atomic>::fetch_and(self, value, order)
+            }
+
+            fn compare_exchange_weak(
+                &self,
+                current: $word,
+                new: $word,
+                success: Ordering,
+                failure: Ordering,
+            ) -> Result<$word, $word> {
+                <$atomic>::compare_exchange_weak(self, current, new, success, failure)
+            }
+        }
+    )+};
+}
+
+impl_word! {
+    u8, AtomicU8, U8;
+    u16, AtomicU16, U16;
+    u32, AtomicU32, U32;
+    u64, AtomicU64, U64;
+}
+
+impl Word for u128 {
+    const WIDTH: Width = Width::U128;
+    type Atomic = Atomic128;
+
+    fn truncate(value: u128) -> Self {
+        value
+    }
+
+    fn wrapping_add(self, other: Self) -> Self {
+        u128::wrapping_add(self, other)
+    }
+
+    fn wrapping_sub(self, other: Self) -> Self {
+        u128::wrapping_sub(self, other)
+    }
+}
+
+/// A 128-bit atomic built on a single compare-exchange. On x86_64
+/// that's `lock cmpxchg16b` when the CPU has it; everywhere else
+/// it's a spinlock next to the value. Orderings are ignored, since
+/// both are at least as strong as anything asked for.
+#[derive(Default)]
+#[repr(C, align(16))]
+pub struct Atomic128 {
+    value: UnsafeCell<u128>,
+    locked: AtomicBool,
+}
+
+// SAFETY: `value` is only touched by `cmpxchg16b` or under `locked`.
+unsafe impl Sync for Atomic128 {}
+
+impl Atomic128 {
+    fn compare_exchange(&self, current: u128, new: u128) -> Result<u128, u128> {
+        #[cfg(target_arch = "x86_64")]
+        if std::arch::is_x86_feature_detected!("cmpxchg16b") {
+            // SAFETY: the CPU has the instruction, and `value` is
+            // 16-byte aligned.
+            return unsafe { cmpxchg16b(self.value.get(), current, new) };
+        }
+
+        while self.locked.swap(true, Ordering::Acquire) {
+            hint::spin_loop();
+        }
+        // SAFETY: we hold the lock.
+        let value = unsafe { &mut *self.value.get() };
+        let previous = *value;
+        if previous == current {
+            *value = new;
+        }
+        self.locked.store(false, Ordering::Release);
+
+        match previous == current {
+            true => Ok(previous),
+            false => Err(previous),
+        }
+    }
+
+    /// Replaces the value with `f(value)` and returns what it was.
+    fn fetch_update(&self, f: impl Fn(u128) -> u128) -> u128 {
+        let mut current = 0;
+        loop {
+            match self.compare_exchange(current, f(current)) {
+                Ok(previous) => return previous,
+                Err(actual) => current = actual,
+            }
+        }
+    }
+}
+
+/// Compares the 16 bytes at `ptr` to `current` and, if they match,
+/// replaces them with `new`. Returns what was there either way.
+///
+/// # Safety
+///
+/// The CPU must support `cmpxchg16b` and `ptr` must be valid and
+/// 16-byte aligned.
+#[cfg(target_arch = "x86_64")]
+unsafe fn cmpxchg16b(ptr: *mut u128, current: u128, new: u128) -> Result<u128, u128> {
+    let (low, high): (u64, u64);
+    let swapped: u8;
+    // `rbx` is reserved by LLVM, so the low half of `new` goes in
+    // through `rsi` and is swapped in and out around the
+    // instruction. Every other operand gets an explicit register
+    // too: left to the allocator, the pointer or the flag could
+    // land in `rbx` and get clobbered by the swap.
+    std::arch::asm!(
+        "xchg rsi, rbx",
+        "lock cmpxchg16b xmmword ptr [rdi]",
+        "sete cl",
+        "mov rbx, rsi",
+        in("rdi") ptr,
+        inout("rsi") new as u64 => _,
+        lateout("cl") swapped,
+        in("rcx") (new >> 64) as u64,
+        inout("rax") current as u64 => low,
+        inout("rdx") (current >> 64) as u64 => high,
+        options(nostack),
+    );
+    let previous = (high as u128) << 64 | low as u128;
+    match swapped {
+        0 => Err(previous),
+        _ => Ok(previous),
+    }
+}
+
+impl AtomicWord<u128> for Atomic128 {
+    fn load(&self, _: Ordering) -> u128 {
+        // Swapping zero for zero never changes anything, and
+        // either way we learn the value.
+        match self.compare_exchange(0, 0) {
+            Ok(value) | Err(value) => value,
+        }
+    }
+
+    fn store(&self, value: u128, _: Ordering) {
+        self.fetch_update(|_| value);
+    }
+
+    fn swap(&self, value: u128, _: Ordering) -> u128 {
+        self.fetch_update(|_| value)
+    }
+
+    fn fetch_xor(&self, value: u128, _: Ordering) -> u128 {
+        self.fetch_update(|current| current ^ value)
+    }
+
+    fn fetch_add(&self, value: u128, _: Ordering) -> u128 {
+        self.fetch_update(|current| current.wrapping_add(value))
+    }
+
+    fn fetch_sub(&self, value: u128, _: Ordering) -> u128 {
+        self.fetch_update(|current| current.wrapping_sub(value))
+    }
+
+    fn fetch_or(&self, value: u128, _: Ordering) -> u128 {
+        self.fetch_update(|current| current | value)
+    }
+
+    fn fetch_and(&self, value: u128, _: Ordering) -> u128 {
+        self.fetch_update(|current| current & value)
+    }
+
+    fn compare_exchange_weak(
+        &self,
+        current: u128,
+        new: u128,
+        _: Ordering,
+        _: Ordering,
+    ) -> Result<u128, u128> {
+        self.compare_exchange(current, new)
+    }
+}
@@ -0,0 +1,168 @@
+//! What each thread does with its random values, and what the
+//! final value has to be if no update was lost.
+
+use crate::{cli::Config, harness, op, race::Race};
+use clap::ValueEnum;
+use serde::Serialize;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Workload {
+    /// Xor every value in `reps` times. Must end at zero.
+    Xor,
+    /// Add every value, then subtract it again. Must end at zero.
+    AddSub,
+    /// Add every value in `reps` times. Must end at the wrapping
+    /// sum of everything added.
+    Add,
+    /// Or every value in once, then or in zero for the rest of the
+    /// reps. Every value is a bit no other value has, so each one
+    /// only gets a single chance to land. Must end at the or of
+    /// every value.
+    Or,
+    /// Or every value in, then and its complement to clear it
+    /// again. Must end at zero.
+    OrAnd,
+    /// Swap every value in `reps` times. Must end at the last value
+    /// of one of the threads.
+    Swap,
+}
+
+impl fmt::Display for Workload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().expect("no skipped variants");
+        f.write_str(value.get_name())
+    }
+}
+
+/// A [`Workload`] as a type, so the hot loop is monomorphized
+/// over it.
+pub trait Steps: 'static {
+    const WORKLOAD: Workload;
+
+    /// Does rep number `rep` of this workload for `operand`.
+    fn step<R: Race>(race: &R, rep: usize, operand: R::Word);
+
+    /// Does every rep for `operand`, one after another.
+    fn phase<R: Race>(race: &R, operand: R::Word, reps: usize) {
+        for rep in 0..reps {
+            Self::step(race, rep, operand);
+        }
+    }
+}
+
+pub struct Xors;
+pub struct AddSubs;
+pub struct Adds;
+pub struct Ors;
+pub struct OrAnds;
+pub struct Swaps;
+
+impl Steps for Xors {
+    const WORKLOAD: Workload = Workload::Xor;
+
+    fn step<R: Race>(race: &R, _: usize, operand: R::Word) {
+        race.apply::<op::Xor>(operand);
+    }
+}
+
+impl Steps for AddSubs {
+    const WORKLOAD: Workload = Workload::AddSub;
+
+    fn step<R: Race>(race: &R, rep: usize, operand: R::Word) {
+        if rep.is_multiple_of(2) {
+            race.apply::<op::Add>(operand);
+        } else {
+            race.apply::<op::Sub>(operand);
+        }
+    }
+}
+
+impl Steps for Adds {
+    const WORKLOAD: Workload = Workload::Add;
+
+    fn step<R: Race>(race: &R, _: usize, operand: R::Word) {
+        race.apply::<op::Add>(operand);
+    }
+}
+
+impl Steps for Ors {
+    const WORKLOAD: Workload = Workload::Or;
+
+    /// Oring zero changes nothing, but it's still a read-modify-
+    /// write, so it can write back a value that's missing someone
+    /// else's bit. Oring the bit again would set it right back.
+    fn step<R: Race>(race: &R, rep: usize, operand: R::Word) {
+        match rep {
+            0 => race.apply::<op::Or>(operand),
+            _ => race.apply::<op::Or>(R::Word::default()),
+        }
+    }
+}
+
+impl Steps for OrAnds {
+    const WORKLOAD: Workload = Workload::OrAnd;
+
+    fn step<R: Race>(race: &R, rep: usize, operand: R::Word) {
+        if rep.is_multiple_of(2) {
+            race.apply::<op::Or>(operand);
+        } else {
+            race.apply::<op::And>(!operand);
+        }
+    }
+}
+
+impl Steps for Swaps {
+    const WORKLOAD: Workload = Workload::Swap;
+
+    fn step<R: Race>(race: &R, _: usize, operand: R::Word) {
+        race.apply::<op::Swap>(operand);
+    }
+}
+
+/// What the final value has to be for a given run.
+#[derive(Debug, Clone)]
+pub enum Expectation {
+    Exactly(u128),
+    OneOf(Vec<u128>),
+}
+
+impl Expectation {
+    /// Replays every thread's operands to work out the answer.
+    pub fn new(config: &Config) -> Self {
+        let width = config.width;
+        let per_thread = || (0..config.threads).map(|t| harness::operands(config, t));
+
+        match config.workload {
+            Workload::Xor | Workload::AddSub | Workload::OrAnd => Self::Exactly(0),
+            Workload::Add => {
+                let sum = per_thread().flatten().fold(0u128, |sum, n| {
+                    sum.wrapping_add(n.wrapping_mul(config.reps as u128))
+                });
+                Self::Exactly(sum & width.mask())
+            }
+            Workload::Or => Self::Exactly(per_thread().flatten().fold(0, |acc, n| acc | n)),
+            Workload::Swap => {
+                let last: Vec<_> = per_thread().filter_map(Iterator::last).collect();
+                match last.is_empty() {
+                    true => Self::Exactly(0),
+                    false => Self::OneOf(last),
+                }
+            }
+        }
+    }
+
+    /// The bits that are wrong in `value`, compared to the closest
+    /// acceptable answer. Zero means nothing was lost.
+    pub fn error(&self, value: u128) -> u128 {
+        match self {
+            Self::Exactly(expected) => value ^ expected,
+            Self::OneOf(expected) => expected
+                .iter()
+                .map(|e| value ^ e)
+                .min_by_key(|error| error.count_ones())
+                .unwrap_or(value),
+        }
+    }
+}